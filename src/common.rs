@@ -1,46 +1,325 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
+use serde::Serialize;
 use serde_json::Value;
-use std::{fs, path::PathBuf};
+use serde_json::ser::{PrettyFormatter, Serializer};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::constants::edge::USER_DATA_PATHS;
+use crate::error::Error;
+use crate::keypath;
+use crate::lock;
+use crate::metrics;
+use crate::repair::{self, Repair};
+use crate::report::{ApplyReport, FileOutcome};
+use crate::safety::{self, Verdict};
+use crate::secure_prefs;
+use crate::state::{OriginalValue, State};
+use crate::version::{self, EdgeVersion, VersionRange};
+
+/// 原文件的 JSON 书写格式，写回时保持一致
+///
+/// Edge 写出的 `Preferences` / `Local State` 是单行紧凑格式；
+/// 被其他工具格式化过的文件则保留其缩进。键顺序由 serde_json 的 `preserve_order` 保证。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct JsonStyle {
+    /// 缩进字符串，`None` 表示单行紧凑格式
+    indent: Option<String>,
+    /// 文件是否以换行结尾
+    trailing_newline: bool,
+}
+
+impl JsonStyle {
+    pub(crate) fn detect(content: &str) -> Self {
+        let body = content.trim_end();
+        let indent = body.contains('\n').then(|| {
+            body.lines()
+                .skip(1)
+                .map(|line| &line[..line.len() - line.trim_start().len()])
+                .find(|indent| !indent.is_empty())
+                .unwrap_or("  ")
+                .to_string()
+        });
+
+        Self {
+            indent,
+            trailing_newline: content.ends_with('\n'),
+        }
+    }
+
+    pub(crate) fn serialize(&self, json: &Value) -> Result<String> {
+        let mut out = Vec::new();
+        match &self.indent {
+            None => serde_json::to_writer(&mut out, json)?,
+            Some(indent) => {
+                let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+                json.serialize(&mut Serializer::with_formatter(&mut out, formatter))?;
+            }
+        }
+        if self.trailing_newline {
+            out.push(b'\n');
+        }
+        Ok(String::from_utf8(out)?)
+    }
+}
+
+/// 处理单个配置文件时的上下文
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct PatchContext<'a> {
+    /// 文件所属 Edge 的版本，随修改记录到状态数据库
+    pub edge_version: Option<&'a EdgeVersion>,
+    /// 文件无法解析时是否尝试从备份恢复（见 [`FixOptions::repair`]）
+    pub repair: bool,
+}
 
 /// 处理单个 JSON 配置文件
 ///
 /// # 参数
 /// - `path`: 文件路径
 /// - `file_type`: 文件类型描述（用于日志）
+/// - `tracked_keys`: `modify_fn` 可能修改的键路径，首次修改前的值会记录到 `state`
+/// - `state`: 状态数据库
+/// - `ctx`: Edge 版本与修复选项
 /// - `modify_fn`: 修改函数，返回 true 表示进行了修改
 ///
-/// # 返回
-/// - `Ok(true)`: 文件已修改并保存
-/// - `Ok(false)`: 文件未修改（不存在或无需修改）
-fn process_json_file(
+/// 文件不存在时返回 [`FileOutcome::Unchanged`]；失败会记录到状态数据库。
+pub(crate) fn process_json_file(
     path: &PathBuf,
     file_type: &str,
-    modify_fn: impl FnOnce(&mut Value) -> bool,
-) -> Result<bool> {
+    tracked_keys: &[&str],
+    state: &mut State,
+    ctx: PatchContext,
+    modify_fn: impl Fn(&mut Value) -> bool,
+) -> FileOutcome {
     if !path.exists() {
-        return Ok(false);
+        return FileOutcome::Unchanged;
     }
 
-    let content = fs::read_to_string(path)
-        .with_context(|| format!("Failed to read {} at {}", file_type, path.display()))?;
+    let state_key = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+    let result = safety::check(path).and_then(|verdict| {
+        let path = match verdict {
+            Verdict::Safe(real) => real,
+            Verdict::Refused(reason) => {
+                log::warn!(
+                    "🔒 Refusing to patch {file_type} at {}: {reason}",
+                    path.display()
+                );
+                return Ok(FileOutcome::Unsafe(reason));
+            }
+        };
+        let path = path.as_path();
+        // 与其他 helper 进程（如 `run` 与一次性的 `apply`）互斥，修复也在锁内进行
+        let _lock = lock::lock_config_file(path)?;
+        let patch = |state: &mut State| {
+            patch_json_file(
+                path,
+                &state_key,
+                file_type,
+                tracked_keys,
+                state,
+                ctx.edge_version,
+                &modify_fn,
+            )
+        };
+        match patch(state) {
+            Err(e @ Error::Parse { .. }) if ctx.repair => {
+                match repair_json_file(path, &state_key, file_type, state) {
+                    Some(Repair::Restored { .. }) => patch(state).map(FileOutcome::from),
+                    Some(Repair::Quarantined(_)) => Ok(FileOutcome::Skipped(
+                        "quarantined corrupt file; Edge will regenerate it".to_string(),
+                    )),
+                    None => Err(e),
+                }
+            }
+            result => result.map(FileOutcome::from),
+        }
+    });
+
+    result.unwrap_or_else(|e| {
+        if matches!(e, Error::Parse { .. }) && !ctx.repair {
+            log::info!(
+                "💡 {file_type} at {} is corrupt; pass --repair to restore it from a backup",
+                path.display()
+            );
+        }
+        state.record_failure(&state_key, &format!("[{}] {}", e.code(), e.message()));
+        if let Err(save_err) = state.save() {
+            log::warn!("⚠️ Failed to update state database: {save_err:#}");
+        }
+        FileOutcome::Failed(e)
+    })
+}
 
-    let mut json: Value = serde_json::from_str(&content)
-        .with_context(|| format!("Failed to parse JSON at {}", path.display()))?;
+/// 修复无法解析的配置文件并记录每一步；修复失败时返回 `None`
+fn repair_json_file(
+    path: &Path,
+    state_key: &Path,
+    file_type: &str,
+    state: &State,
+) -> Option<Repair> {
+    log::warn!(
+        "🩹 {file_type} at {} is corrupt, trying to repair it",
+        path.display()
+    );
+    match repair::repair(&state.backup_dir(), state_key, path) {
+        Ok(Repair::Restored { from, quarantined }) => {
+            log::info!(
+                "🩹 Moved corrupt {file_type} to {} and restored it from {}",
+                quarantined.display(),
+                from.display()
+            );
+            Some(Repair::Restored { from, quarantined })
+        }
+        Ok(Repair::Quarantined(quarantined)) => {
+            log::warn!(
+                "🩹 No usable backup of {file_type} at {}; moved it to {} so Edge regenerates it",
+                path.display(),
+                quarantined.display()
+            );
+            Some(Repair::Quarantined(quarantined))
+        }
+        Err(e) => {
+            log::error!(
+                "❌ Failed to repair {file_type} at {}: {e:#}",
+                path.display()
+            );
+            None
+        }
+    }
+}
 
-    let modified = modify_fn(&mut json);
+/// 读取、修改并写回 JSON 文件，同时把原始值和修改历史记录到状态数据库
+///
+/// 调用方需持有该文件的配置文件锁。
+fn patch_json_file(
+    path: &Path,
+    state_key: &Path,
+    file_type: &str,
+    tracked_keys: &[&str],
+    state: &mut State,
+    edge_version: Option<&EdgeVersion>,
+    modify_fn: impl Fn(&mut Value) -> bool,
+) -> Result<bool, Error> {
+    let mut attempt = 1;
+    let (content, json, before) = loop {
+        let stamp = file_stamp(path);
+        let content = safety::read_to_string(path).map_err(|e| Error::read(path, e))?;
+
+        let mut json: Value = serde_json::from_str(&content).map_err(|source| Error::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let before: Vec<OriginalValue> = tracked_keys
+            .iter()
+            .map(|key| OriginalValue::from_lookup(keypath::get(&json, key)))
+            .collect();
+
+        if !modify_fn(&mut json) {
+            return Ok(false);
+        }
 
-    if modified {
-        let new_content = serde_json::to_string_pretty(&json)?;
-        fs::write(path, new_content)
-            .with_context(|| format!("Failed to write {} at {}", file_type, path.display()))?;
+        // 锁只约束 helper 自身，Edge 或其他工具仍可能在读取之后改写文件
+        if file_stamp(path) == stamp {
+            break (content, json, before);
+        }
+        if attempt >= MAX_PATCH_ATTEMPTS {
+            return Err(Error::Other(anyhow::anyhow!(
+                "{} at {} kept changing while being patched",
+                file_type,
+                path.display()
+            )));
+        }
         log::info!(
-            "✅ Edge Copilot region fix applied to {} at {}",
+            "{} at {} changed while being patched, retrying",
             file_type,
             path.display()
         );
+        attempt += 1;
+    };
+
+    // 先记录原始值再写入文件，保证 revert 总能找到修改前的值
+    let mut recorded = false;
+    let mut previous = BTreeMap::new();
+    for (key, original) in tracked_keys.iter().zip(before) {
+        if OriginalValue::from_lookup(keypath::get(&json, key)) != original {
+            recorded |= state.record_original(state_key, key, original.clone());
+            previous.insert(key.to_string(), original);
+        }
+    }
+    if recorded {
+        state.save()?;
     }
 
-    Ok(modified)
+    // 保留写入前的内容，文件日后损坏时 `--repair` 可以从中恢复
+    if let Err(e) = repair::save_backup(&state.backup_dir(), state_key, &content) {
+        log::warn!(
+            "⚠️ Failed to back up {file_type} at {}: {e:#}",
+            path.display()
+        );
+    }
+
+    let new_content = JsonStyle::detect(&content).serialize(&json)?;
+    if let Err(e) = safety::write(path, new_content) {
+        metrics::record_write_failure(file_type);
+        return Err(Error::write(path, e));
+    }
+    metrics::record_patch(file_type);
+    log::info!(
+        "✅ Edge Copilot region fix applied to {} at {}",
+        file_type,
+        path.display()
+    );
+
+    let edge_version = edge_version.map(EdgeVersion::to_string);
+    if let Some(resets) = state.record_patch(state_key, previous, edge_version) {
+        log::info!(
+            "🔁 Edge had reset {} at {} ({resets} time(s) so far)",
+            file_type,
+            path.display()
+        );
+    }
+    if let Err(e) = state.save() {
+        log::warn!("⚠️ Failed to update state database: {e:#}");
+    }
+    Ok(true)
+}
+
+/// 文件在读取与写回之间被改写时，重新读取并修改的最大次数
+const MAX_PATCH_ATTEMPTS: u32 = 3;
+
+/// 文件的修改时间与大小，用于发现读取之后的改写
+fn file_stamp(path: &Path) -> Option<(std::time::SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// [`apply_fix`] 的选项
+#[derive(Debug, Clone, Default)]
+pub struct FixOptions {
+    /// 查找 Edge 用户数据目录的根目录，`None` 表示当前用户的主目录
+    pub home: Option<PathBuf>,
+    /// 相对于根目录的 Edge 用户数据目录，`None` 表示当前平台的默认列表
+    /// （[`USER_DATA_PATHS`]）；绝对路径不受根目录影响
+    pub user_data_paths: Option<Vec<PathBuf>>,
+    /// 同时按 [`SECURE_RULES`](crate::secure_prefs::SECURE_RULES) 修改 Secure Preferences 中
+    /// 已存在的对应条目（可选，默认只检测并报告）
+    pub secure_preferences: bool,
+    /// 配置文件无法解析时尝试修复：先用 helper 的备份或 Edge 留下的 `.bak` 副本替换，
+    /// 都不可用时把损坏的文件移走，由 Edge 重新生成（可选，默认只报告错误）
+    pub repair: bool,
+    /// 状态数据库路径，`None` 表示安装目录下的 `state.json`
+    pub state_file: Option<PathBuf>,
+}
+
+/// 在某个根目录下发现的 Edge 配置文件
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EdgePaths {
+    /// 各 Edge 版本的 `Local State` 文件
+    pub local_state: Vec<PathBuf>,
+    /// 各 Profile（`Default`、`Profile N`）的 `Preferences` 文件
+    pub preferences: Vec<PathBuf>,
 }
 
 /// 应用 Edge Copilot 区域修复
@@ -50,45 +329,191 @@ fn process_json_file(
 /// 2. 修改 `Local State` 文件中的 `variations_country` 为 "US"
 /// 3. 修改各 Profile 的 `Preferences` 文件，设置 `chat_ip_eligibility_status` 为 true
 ///
+/// 单个配置文件的读写失败、损坏或未通过安全检查不会中断处理，而是记录在返回的
+/// [`ApplyReport`] 中（见 [`FileOutcome`]）。
+///
 /// # 错误
-/// 返回 `Err` 如果无法读取或写入配置文件
-pub fn apply_fix() -> Result<()> {
-    let (local_state_paths, prefs_paths) = get_all_paths()?;
-
-    let mut found_existing = false;
-    let mut any_modified = false;
+/// 只有无法定位 Edge 配置文件（如无法确定主目录）或无法读取状态数据库时返回 `Err`
+pub fn apply_fix(options: &FixOptions) -> Result<ApplyReport> {
+    let EdgePaths {
+        local_state: local_state_paths,
+        preferences: prefs_paths,
+    } = get_all_paths(options)?;
+    let state_file = options
+        .state_file
+        .clone()
+        .unwrap_or_else(State::default_path);
+    let mut state = State::load(&state_file)?;
+
+    let mut versions = EdgeVersions::default();
+    let mut report = ApplyReport::default();
 
     // 处理 Local State 文件
     for local_state_path in local_state_paths {
-        found_existing = true;
-        if process_json_file(&local_state_path, "Local State", |json| {
-            patch_variations_country(json)
-        })? {
-            any_modified = true;
-        }
+        let edge_version = versions.of(&local_state_path);
+        let ctx = PatchContext {
+            edge_version: edge_version.as_ref(),
+            repair: options.repair,
+        };
+        let outcome = apply_rules(
+            &local_state_path,
+            "Local State",
+            LOCAL_STATE_RULES,
+            &mut state,
+            ctx,
+        );
+        report.push(local_state_path, "Local State", outcome);
     }
 
     // 处理 Preferences 文件（所有 Profile）
     for prefs_path in prefs_paths {
-        found_existing = true;
-        if process_json_file(&prefs_path, "Preferences", |json| {
-            set_chat_ip_eligibility_status(json)
-        })? {
-            any_modified = true;
+        let edge_version = versions.of(&prefs_path);
+        let ctx = PatchContext {
+            edge_version: edge_version.as_ref(),
+            repair: options.repair,
+        };
+        let outcome = apply_rules(
+            &prefs_path,
+            "Preferences",
+            PREFERENCES_RULES,
+            &mut state,
+            ctx,
+        );
+        let prefs_modified = matches!(outcome, FileOutcome::Patched);
+
+        // 检查 MAC 保护并按需修改 Secure Preferences
+        let secure_outcome = prefs_path.parent().and_then(|profile_dir| {
+            let outcome = secure_prefs::process_profile(
+                profile_dir,
+                &mut state,
+                ctx,
+                prefs_modified,
+                options.secure_preferences,
+            )?;
+            Some((
+                profile_dir.join(secure_prefs::SECURE_PREFERENCES_FILE),
+                outcome,
+            ))
+        });
+
+        report.push(prefs_path, "Preferences", outcome);
+        if let Some((secure_path, outcome)) = secure_outcome {
+            report.push(secure_path, secure_prefs::SECURE_PREFERENCES_FILE, outcome);
         }
     }
 
-    if !found_existing {
+    if report.files.is_empty() {
         log::warn!("⚠️ Edge configuration files not found in known locations.");
-    } else if !any_modified {
+    } else if report.patched() == 0 && report.is_success() {
         log::info!(
             "ℹ️ No changes needed: variations_country already US and chat_ip_eligibility_status already set."
         );
     }
 
-    Ok(())
+    if report.is_success() {
+        metrics::record_success();
+    }
+    Ok(report)
+}
+
+/// 修复规则：修改的键、适用的 Edge 版本范围与修改函数
+pub(crate) struct PatchRule {
+    /// 修改函数可能修改的键路径
+    pub key: &'static str,
+    /// 规则适用的 Edge 版本范围
+    pub versions: VersionRange,
+    /// 修改函数，返回 true 表示进行了修改
+    pub apply: fn(&mut Value) -> bool,
+}
+
+/// `Local State` 的修复规则
+pub(crate) const LOCAL_STATE_RULES: &[PatchRule] = &[PatchRule {
+    key: VARIATIONS_COUNTRY_KEY,
+    versions: VersionRange::ALL,
+    apply: patch_variations_country,
+}];
+
+/// 各 Profile `Preferences` 的修复规则
+pub(crate) const PREFERENCES_RULES: &[PatchRule] = &[PatchRule {
+    key: CHAT_IP_ELIGIBILITY_KEY,
+    versions: VersionRange::ALL,
+    apply: set_chat_ip_eligibility_status,
+}];
+
+/// 按 Edge 版本筛选适用的规则并应用到文件
+fn apply_rules(
+    path: &PathBuf,
+    file_type: &str,
+    rules: &[PatchRule],
+    state: &mut State,
+    ctx: PatchContext,
+) -> FileOutcome {
+    let rules = applicable_rules(rules, ctx.edge_version);
+    if rules.is_empty() {
+        let version = ctx
+            .edge_version
+            .map_or_else(|| "unknown".to_string(), EdgeVersion::to_string);
+        return FileOutcome::Skipped(format!("no rules apply to Edge {version}"));
+    }
+
+    let keys: Vec<&str> = rules.iter().map(|rule| rule.key).collect();
+    process_json_file(path, file_type, &keys, state, ctx, |json| {
+        let mut modified = false;
+        for rule in &rules {
+            modified |= (rule.apply)(json);
+        }
+        modified
+    })
+}
+
+/// 适用于该 Edge 版本的规则；跳过的规则会记录日志
+fn applicable_rules<'a>(
+    rules: &'a [PatchRule],
+    edge_version: Option<&EdgeVersion>,
+) -> Vec<&'a PatchRule> {
+    rules
+        .iter()
+        .filter(|rule| {
+            let applies = rule.versions.contains(edge_version);
+            if !applies && let Some(version) = edge_version {
+                log::info!(
+                    "Skipping {}: applies to Edge {}, found {version}",
+                    rule.key,
+                    rule.versions
+                );
+            }
+            applies
+        })
+        .collect()
+}
+
+/// 各用户数据目录的 Edge 版本（每次 [`apply_fix`] 只检测一次）
+#[derive(Default)]
+struct EdgeVersions(BTreeMap<PathBuf, Option<EdgeVersion>>);
+
+impl EdgeVersions {
+    /// 配置文件所属 Edge 的版本；首次检测到比验证过的更新的版本时发出警告
+    fn of(&mut self, config_file: &Path) -> Option<EdgeVersion> {
+        let user_data_dir = version::user_data_dir_of(config_file)?;
+        self.0
+            .entry(user_data_dir.to_path_buf())
+            .or_insert_with(|| {
+                let detected = version::detect(user_data_dir);
+                if let Some(version) = &detected {
+                    version::warn_if_untested(version, user_data_dir);
+                }
+                detected
+            })
+            .clone()
+    }
 }
 
+/// `patch_variations_country` 修改的键路径
+pub(crate) const VARIATIONS_COUNTRY_KEY: &str = "variations_country";
+
+/// `set_chat_ip_eligibility_status` 修改的键路径
+pub(crate) const CHAT_IP_ELIGIBILITY_KEY: &str = "browser.chat_ip_eligibility_status";
+
 /// 修改 Local State 中的 variations_country 字段为 "US"
 fn patch_variations_country(json: &mut Value) -> bool {
     if let Some(obj) = json.as_object_mut() {
@@ -140,47 +565,40 @@ fn set_chat_ip_eligibility_status(json: &mut Value) -> bool {
 }
 
 /// 获取所有需要修改的文件路径
-/// 返回 (Local State 路径列表, Preferences 路径列表)
-fn get_all_paths() -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
-    let home = dirs::home_dir().context("Could not determine home directory")?;
-
-    #[cfg(target_os = "macos")]
-    let user_data_paths: &[&str] = &[
-        "Library/Application Support/Microsoft Edge",
-        "Library/Application Support/Microsoft Edge Beta",
-        "Library/Application Support/Microsoft Edge Dev",
-        "Library/Application Support/Microsoft Edge Canary",
-    ];
-
-    #[cfg(target_os = "linux")]
-    let user_data_paths: &[&str] = &[
-        ".config/microsoft-edge",
-        ".config/microsoft-edge-beta",
-        ".config/microsoft-edge-dev",
-        ".config/microsoft-edge-canary",
-    ];
-
-    #[cfg(target_os = "windows")]
-    let user_data_paths: &[&str] = &[
-        "AppData/Local/Microsoft/Edge/User Data",
-        "AppData/Local/Microsoft/Edge Beta/User Data",
-        "AppData/Local/Microsoft/Edge Dev/User Data",
-        "AppData/Local/Microsoft/Edge SxS/User Data",
-    ];
-
-    collect_edge_paths(&home, user_data_paths)
+fn get_all_paths(options: &FixOptions) -> Result<EdgePaths> {
+    let home = match &options.home {
+        Some(home) if !home.is_dir() => {
+            return Err(Error::Discovery(format!(
+                "Edge search root {} does not exist",
+                home.display()
+            ))
+            .into());
+        }
+        Some(home) => home.clone(),
+        None => home_dir()?,
+    };
+    match &options.user_data_paths {
+        Some(user_data_paths) => collect_edge_paths(&home, user_data_paths),
+        None => collect_edge_paths(&home, USER_DATA_PATHS),
+    }
+}
+
+/// 当前用户的主目录
+fn home_dir() -> Result<PathBuf, Error> {
+    dirs::home_dir().ok_or_else(|| Error::Discovery("Could not determine home directory".into()))
 }
 
 /// 从指定的用户数据目录收集 Edge 配置文件路径
-fn collect_edge_paths(
-    home: &std::path::Path,
-    user_data_paths: &[&str],
-) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+///
+/// `user_data_paths` 为相对于 `home` 的用户数据目录（如
+/// [`constants::edge::USER_DATA_PATHS`](crate::constants::edge::USER_DATA_PATHS)），
+/// 不存在的目录会被跳过。
+pub fn collect_edge_paths(home: &Path, user_data_paths: &[impl AsRef<Path>]) -> Result<EdgePaths> {
     let mut local_state_paths = Vec::new();
     let mut prefs_paths = Vec::new();
 
     for user_data_path in user_data_paths {
-        let user_data = home.join(user_data_path);
+        let user_data = home.join(user_data_path.as_ref());
         if !user_data.exists() {
             continue;
         }
@@ -210,7 +628,10 @@ fn collect_edge_paths(
         }
     }
 
-    Ok((local_state_paths, prefs_paths))
+    Ok(EdgePaths {
+        local_state: local_state_paths,
+        preferences: prefs_paths,
+    })
 }
 
 #[cfg(test)]
@@ -302,4 +723,165 @@ mod tests {
         let mut value = json!("not an object");
         assert!(!set_chat_ip_eligibility_status(&mut value));
     }
+
+    #[test]
+    fn test_json_style_keeps_compact_format_and_key_order() {
+        let content = r#"{"zeta":1,"browser":{"b":true,"a":false},"alpha":[1,2]}"#;
+        let style = JsonStyle::detect(content);
+        assert_eq!(style.indent, None);
+
+        let mut json: Value = serde_json::from_str(content).unwrap();
+        assert!(set_chat_ip_eligibility_status(&mut json));
+        assert_eq!(
+            style.serialize(&json).unwrap(),
+            r#"{"zeta":1,"browser":{"b":true,"a":false,"chat_ip_eligibility_status":true},"alpha":[1,2]}"#
+        );
+    }
+
+    #[test]
+    fn test_json_style_keeps_pretty_indent_and_trailing_newline() {
+        let content = "{\n   \"variations_country\": \"CN\",\n   \"b\": 1\n}\n";
+        let style = JsonStyle::detect(content);
+        assert_eq!(style.indent.as_deref(), Some("   "));
+        assert!(style.trailing_newline);
+
+        let mut json: Value = serde_json::from_str(content).unwrap();
+        assert!(patch_variations_country(&mut json));
+        assert_eq!(
+            style.serialize(&json).unwrap(),
+            "{\n   \"variations_country\": \"US\",\n   \"b\": 1\n}\n"
+        );
+    }
+
+    /// 在临时目录中创建一个 Edge 用户数据目录
+    fn create_user_data(root: &Path, user_data: &str) -> PathBuf {
+        let dir = root.join(user_data);
+        fs::create_dir_all(dir.join("Default")).unwrap();
+        fs::create_dir_all(dir.join("Profile 1")).unwrap();
+        fs::create_dir_all(dir.join("System Profile")).unwrap();
+        fs::write(dir.join("Local State"), r#"{"variations_country":"CN"}"#).unwrap();
+        for profile in ["Default", "Profile 1", "System Profile"] {
+            fs::write(dir.join(profile).join("Preferences"), r#"{"browser":{}}"#).unwrap();
+        }
+        dir
+    }
+
+    fn read_json(path: &Path) -> Value {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_collect_edge_paths_custom_user_data_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = create_user_data(root.path(), "edge-data");
+
+        let paths = collect_edge_paths(root.path(), &["edge-data", "missing"]).unwrap();
+        assert_eq!(paths.local_state, vec![dir.join("Local State")]);
+
+        let mut preferences = paths.preferences;
+        preferences.sort();
+        assert_eq!(
+            preferences,
+            vec![
+                dir.join("Default/Preferences"),
+                dir.join("Profile 1/Preferences")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_fix_with_injected_home() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = create_user_data(root.path(), USER_DATA_PATHS[0]);
+
+        apply_fix(&FixOptions {
+            home: Some(root.path().to_path_buf()),
+            state_file: Some(root.path().join("state.json")),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            read_json(&dir.join("Local State"))["variations_country"],
+            json!("US")
+        );
+        assert_eq!(
+            read_json(&dir.join("Profile 1/Preferences"))["browser"]["chat_ip_eligibility_status"],
+            json!(true)
+        );
+        assert_eq!(
+            read_json(&dir.join("System Profile/Preferences")),
+            json!({"browser": {}})
+        );
+    }
+
+    #[test]
+    fn test_applicable_rules_respect_version_ranges() {
+        const RULES: &[PatchRule] = &[
+            PatchRule {
+                key: "old.key",
+                versions: VersionRange {
+                    min_major: None,
+                    max_major: Some(120),
+                },
+                apply: patch_variations_country,
+            },
+            PatchRule {
+                key: "new.key",
+                versions: VersionRange {
+                    min_major: Some(121),
+                    max_major: None,
+                },
+                apply: patch_variations_country,
+            },
+        ];
+        let keys = |version: Option<&str>| -> Vec<&str> {
+            let version = version.and_then(EdgeVersion::parse);
+            applicable_rules(RULES, version.as_ref())
+                .iter()
+                .map(|rule| rule.key)
+                .collect()
+        };
+
+        assert_eq!(keys(Some("119.0.2151.97")), ["old.key"]);
+        assert_eq!(keys(Some("130.0.2849.80")), ["new.key"]);
+        assert_eq!(keys(None), ["old.key", "new.key"]);
+    }
+
+    #[test]
+    fn test_process_json_file_retries_when_file_changes_underneath() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Local State");
+        fs::write(&path, r#"{"variations_country":"CN"}"#).unwrap();
+        let mut state = State::load(&dir.path().join("state.json")).unwrap();
+
+        let calls = std::cell::Cell::new(0);
+        let outcome = process_json_file(
+            &path,
+            "Local State",
+            &[VARIATIONS_COUNTRY_KEY],
+            &mut state,
+            PatchContext::default(),
+            |json| {
+                calls.set(calls.get() + 1);
+                if calls.get() == 1 {
+                    // 模拟另一个进程在读取之后改写了文件
+                    fs::write(&path, r#"{"variations_country":"SG","profile":{}}"#).unwrap();
+                }
+                patch_variations_country(json)
+            },
+        );
+
+        assert!(matches!(outcome, FileOutcome::Patched));
+        assert_eq!(calls.get(), 2);
+        assert_eq!(
+            read_json(&path),
+            json!({"variations_country": "US", "profile": {}})
+        );
+        let original = &state.files[&path].original;
+        assert_eq!(
+            original[VARIATIONS_COUNTRY_KEY],
+            OriginalValue::Present(json!("SG"))
+        );
+    }
 }
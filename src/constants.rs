@@ -4,6 +4,24 @@ use std::time::{Duration, SystemTime};
 pub const APP_LABEL: &str = "top.qiyuey.edge-copilot-helper";
 pub const BINARY_NAME: &str = "edge-copilot-helper";
 
+/// 单实例锁文件名（位于安装目录下）
+pub const LOCK_FILE: &str = "edge-copilot-helper.lock";
+
+/// 单实例锁持有者信息文件名（位于安装目录下）
+///
+/// 与锁文件分开存放：Windows 上文件锁是强制的，其他进程无法读取被锁定的锁文件。
+pub const LOCK_INFO_FILE: &str = "edge-copilot-helper.lock.info";
+
+/// 停止请求文件名（位于安装目录下，Windows 上 `--replace` 用它请求旧实例退出）
+#[cfg(target_os = "windows")]
+pub const STOP_REQUEST_FILE: &str = "edge-copilot-helper.stop";
+
+/// 配置文件备份目录名（位于状态数据库所在目录下，每个配置文件保留最近几份写入前的内容）
+pub const BACKUPS_DIR: &str = "backups";
+
+/// 状态数据库文件名（位于安装目录下，记录被修改键的原始值）
+pub const STATE_FILE: &str = "state.json";
+
 /// 日志文件保留天数
 pub const LOG_RETENTION_DAYS: u32 = 7;
 
@@ -34,6 +52,33 @@ pub mod edge {
         not(target_os = "macos")
     ))]
     pub const PROCESS_NAMES: &[&str] = &["msedge"];
+
+    /// macOS 上各 Edge 版本的用户数据目录（相对于主目录）
+    #[cfg(target_os = "macos")]
+    pub const USER_DATA_PATHS: &[&str] = &[
+        "Library/Application Support/Microsoft Edge",
+        "Library/Application Support/Microsoft Edge Beta",
+        "Library/Application Support/Microsoft Edge Dev",
+        "Library/Application Support/Microsoft Edge Canary",
+    ];
+
+    /// Linux 上各 Edge 版本的用户数据目录（相对于主目录）
+    #[cfg(target_os = "linux")]
+    pub const USER_DATA_PATHS: &[&str] = &[
+        ".config/microsoft-edge",
+        ".config/microsoft-edge-beta",
+        ".config/microsoft-edge-dev",
+        ".config/microsoft-edge-canary",
+    ];
+
+    /// Windows 上各 Edge 版本的用户数据目录（相对于主目录）
+    #[cfg(target_os = "windows")]
+    pub const USER_DATA_PATHS: &[&str] = &[
+        "AppData/Local/Microsoft/Edge/User Data",
+        "AppData/Local/Microsoft/Edge Beta/User Data",
+        "AppData/Local/Microsoft/Edge Dev/User Data",
+        "AppData/Local/Microsoft/Edge SxS/User Data",
+    ];
 }
 
 /// 清理超过保留天数的旧日志文件
@@ -87,6 +132,10 @@ pub mod paths {
     pub fn binary_path() -> PathBuf {
         install_dir().join(BINARY_NAME)
     }
+
+    pub fn backup_dir() -> PathBuf {
+        install_dir().join(BACKUPS_DIR)
+    }
 }
 
 #[cfg(target_os = "windows")]
@@ -106,6 +155,10 @@ pub mod paths {
     pub fn binary_path() -> PathBuf {
         install_dir().join(format!("{BINARY_NAME}.exe"))
     }
+
+    pub fn backup_dir() -> PathBuf {
+        install_dir().join(BACKUPS_DIR)
+    }
 }
 
 #[cfg(target_os = "linux")]
@@ -137,7 +190,48 @@ pub mod paths {
             .join(format!("{}.service", APP_LABEL))
     }
 
+    pub fn autostart_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| {
+                dirs::home_dir()
+                    .unwrap_or_else(|| PathBuf::from("~"))
+                    .join(".config")
+            })
+            .join("autostart")
+            .join(format!("{}.desktop", APP_LABEL))
+    }
+
     pub fn binary_path() -> PathBuf {
         install_dir().join(BINARY_NAME)
     }
+
+    pub fn backup_dir() -> PathBuf {
+        install_dir().join(BACKUPS_DIR)
+    }
+
+    /// 系统级安装（`install --system`）使用的路径
+    ///
+    /// 每个用户的实例仍然使用上面的用户级路径（日志、锁文件等），
+    /// 只有二进制文件、unit 文件和安装清单是全局共享的。
+    pub mod system {
+        use super::*;
+
+        pub fn binary_path() -> PathBuf {
+            PathBuf::from("/usr/local/bin").join(BINARY_NAME)
+        }
+
+        pub fn unit_path() -> PathBuf {
+            PathBuf::from("/etc/systemd/user").join(format!("{}.service", APP_LABEL))
+        }
+
+        /// 存放系统级安装清单的目录
+        pub fn data_dir() -> PathBuf {
+            PathBuf::from("/var/lib").join(APP_LABEL)
+        }
+
+        /// unit 中每个用户实例的安装目录，`%h` 由用户管理器展开为各自的主目录
+        pub fn user_install_dir() -> PathBuf {
+            PathBuf::from("%h/.local/share").join(APP_LABEL)
+        }
+    }
 }
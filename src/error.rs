@@ -0,0 +1,193 @@
+use std::fmt::Write as _;
+use std::io;
+use std::path::PathBuf;
+
+/// helper 的错误类型
+///
+/// 每个变体有稳定的错误码（出现在日志和 `apply --json` 的输出中）与进程退出码，
+/// 调用方可以据此区分权限问题、文件损坏、锁冲突等情况。
+/// 对外接口仍返回 `anyhow::Result`，用 [`find`] 从错误链中取出本类型。
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// 无法确定 Edge 用户数据目录所在的根目录
+    #[error("{0}")]
+    Discovery(String),
+    /// 文件在处理过程中消失
+    #[error("{} not found", path.display())]
+    NotFound {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// 没有读取或写入权限
+    #[error("Permission denied: {}", path.display())]
+    PermissionDenied {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// 其他 I/O 错误
+    #[error("Failed to access {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// 文件内容不是合法的 JSON
+    #[error("Failed to parse JSON at {}", path.display())]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// 写回文件失败
+    #[error("Failed to write {}", path.display())]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// 单实例锁被占用，或等待配置文件锁超时
+    #[error("{0}")]
+    Lock(String),
+    /// 服务管理器（systemctl、launchctl、reg）操作失败
+    #[error("{0}")]
+    ServiceManager(String),
+    /// 部分配置文件修复失败（详见 [`ApplyReport`](crate::ApplyReport)）
+    #[error("Failed to fix {0} file(s)")]
+    PartialFailure(usize),
+    /// 找到的配置文件全部因安全检查未通过而被拒绝修改
+    #[error("Refused to modify {0} file(s) that failed the security checks")]
+    Refused(usize),
+    /// 其他错误
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl Error {
+    /// 读取文件失败：区分找不到文件、权限不足与其他 I/O 错误
+    pub(crate) fn read(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        let path = path.into();
+        match source.kind() {
+            io::ErrorKind::NotFound => Self::NotFound { path, source },
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied { path, source },
+            _ => Self::Io { path, source },
+        }
+    }
+
+    /// 写入文件失败：权限不足单独归类
+    pub(crate) fn write(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        let path = path.into();
+        match source.kind() {
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied { path, source },
+            _ => Self::Write { path, source },
+        }
+    }
+
+    /// 稳定的错误码
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Discovery(_) => "E_DISCOVERY",
+            Self::NotFound { .. } => "E_NOT_FOUND",
+            Self::PermissionDenied { .. } => "E_PERMISSION",
+            Self::Io { .. } => "E_IO",
+            Self::Parse { .. } => "E_PARSE",
+            Self::Write { .. } => "E_WRITE",
+            Self::Lock(_) => "E_LOCK",
+            Self::ServiceManager(_) => "E_SERVICE",
+            Self::PartialFailure(_) => "E_PARTIAL",
+            Self::Refused(_) => "E_UNSAFE",
+            Self::Other(_) => "E_OTHER",
+        }
+    }
+
+    /// 进程退出码（2 保留给命令行参数错误）
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Other(_) => 1,
+            Self::Discovery(_) => 3,
+            Self::NotFound { .. } => 4,
+            Self::PermissionDenied { .. } => 5,
+            Self::Io { .. } => 6,
+            Self::Parse { .. } => 7,
+            Self::Write { .. } => 8,
+            Self::Lock(_) => 9,
+            Self::ServiceManager(_) => 10,
+            Self::PartialFailure(_) => 11,
+            Self::Refused(_) => 12,
+        }
+    }
+
+    /// 包含全部原因的单行描述，如 `Failed to parse JSON at …: EOF while parsing …`
+    pub fn message(&self) -> String {
+        if let Self::Other(error) = self {
+            return format!("{error:#}");
+        }
+        let mut message = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(cause) = source {
+            let _ = write!(message, ": {cause}");
+            source = cause.source();
+        }
+        message
+    }
+}
+
+/// 错误链中第一个 [`Error`]
+pub fn find(error: &anyhow::Error) -> Option<&Error> {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<Error>())
+}
+
+/// `anyhow::Error` 对应的错误码，没有类型信息时为 `E_OTHER`
+pub fn code_of(error: &anyhow::Error) -> &'static str {
+    find(error).map_or("E_OTHER", Error::code)
+}
+
+/// `anyhow::Error` 对应的进程退出码，没有类型信息时为 1
+pub fn exit_code_of(error: &anyhow::Error) -> u8 {
+    find(error).map_or(1, Error::exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_and_write_errors_are_classified() {
+        let denied = Error::read(
+            "Preferences",
+            io::Error::from(io::ErrorKind::PermissionDenied),
+        );
+        assert_eq!(denied.code(), "E_PERMISSION");
+        assert_eq!(denied.exit_code(), 5);
+
+        let missing = Error::read("Preferences", io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(missing.code(), "E_NOT_FOUND");
+
+        let full = Error::write("Preferences", io::Error::from(io::ErrorKind::StorageFull));
+        assert_eq!(full.code(), "E_WRITE");
+    }
+
+    #[test]
+    fn test_codes_survive_anyhow_context() {
+        let parse = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let error = anyhow::Error::new(Error::Parse {
+            path: "Local State".into(),
+            source: parse,
+        })
+        .context("Failed to apply fix");
+
+        assert_eq!(code_of(&error), "E_PARSE");
+        assert_eq!(exit_code_of(&error), 7);
+        assert!(
+            find(&error)
+                .unwrap()
+                .message()
+                .starts_with("Failed to parse JSON at Local State: EOF")
+        );
+        assert_eq!(exit_code_of(&anyhow::anyhow!("boom")), 1);
+    }
+}
@@ -0,0 +1,100 @@
+use serde_json::{Map, Value};
+
+/// 按以 `.` 分隔的键路径查找值
+pub(crate) fn get<'a>(json: &'a Value, key_path: &str) -> Option<&'a Value> {
+    key_path
+        .split('.')
+        .try_fold(json, |value, segment| value.as_object()?.get(segment))
+}
+
+/// 键路径所在的父对象与最后一段键名
+fn parent_mut<'a>(
+    json: &'a mut Value,
+    key_path: &'a str,
+) -> Option<(&'a mut Map<String, Value>, &'a str)> {
+    let (parent_path, key) = match key_path.rsplit_once('.') {
+        Some((parent_path, key)) => (Some(parent_path), key),
+        None => (None, key_path),
+    };
+
+    let parent = match parent_path {
+        Some(parent_path) => parent_path.split('.').try_fold(json, |value, segment| {
+            value.as_object_mut()?.get_mut(segment)
+        })?,
+        None => json,
+    };
+    Some((parent.as_object_mut()?, key))
+}
+
+/// 替换已存在的键的值；键不存在或已是目标值时不做修改
+pub(crate) fn replace_existing(json: &mut Value, key_path: &str, target: Value) -> bool {
+    match parent_mut(json, key_path).and_then(|(parent, key)| parent.get_mut(key)) {
+        Some(value) if *value != target => {
+            *value = target;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// 设置键的值（已存在的键保持原有位置）；父对象不存在时不做修改
+pub(crate) fn set(json: &mut Value, key_path: &str, target: Value) -> bool {
+    let Some((parent, key)) = parent_mut(json, key_path) else {
+        return false;
+    };
+    if parent.get(key) == Some(&target) {
+        return false;
+    }
+    parent.insert(key.to_string(), target);
+    true
+}
+
+/// 删除键，保持其余键的顺序
+pub(crate) fn remove(json: &mut Value, key_path: &str) -> bool {
+    parent_mut(json, key_path).is_some_and(|(parent, key)| parent.shift_remove(key).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_replace_existing_only_touches_present_keys() {
+        let mut json = json!({ "browser": { "chat_ip_eligibility_status": false } });
+        assert!(replace_existing(
+            &mut json,
+            "browser.chat_ip_eligibility_status",
+            Value::Bool(true)
+        ));
+        assert_eq!(json["browser"]["chat_ip_eligibility_status"], json!(true));
+        assert!(!replace_existing(
+            &mut json,
+            "browser.chat_ip_eligibility_status",
+            Value::Bool(true)
+        ));
+
+        let mut missing = json!({ "extensions": {} });
+        assert!(!replace_existing(
+            &mut missing,
+            "browser.chat_ip_eligibility_status",
+            Value::Bool(true)
+        ));
+        assert_eq!(missing, json!({ "extensions": {} }));
+    }
+
+    #[test]
+    fn test_set_and_remove_keep_key_order() {
+        let mut json = json!({ "a": 1, "variations_country": "US", "z": { "b": 2, "c": 3 } });
+
+        assert!(set(&mut json, "variations_country", json!("CN")));
+        assert!(remove(&mut json, "z.b"));
+        assert!(!remove(&mut json, "z.missing"));
+        assert!(!set(&mut json, "missing.key", json!(1)));
+
+        assert_eq!(
+            serde_json::to_string(&json).unwrap(),
+            r#"{"a":1,"variations_country":"CN","z":{"c":3}}"#
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! Edge Copilot Helper
+//!
+//! 绕过 Microsoft Edge Copilot 区域限制的核心逻辑，供命令行程序和其他工具复用：
+//!
+//! - [`collect_edge_paths`]：在指定根目录下发现各 Edge 版本的 `Local State` 与各 Profile 的 `Preferences`
+//! - [`apply_fix`]：按 [`FixOptions`] 对发现的配置文件应用区域修复
+//! - [`service::render`]：渲染 systemd unit、XDG autostart 条目、LaunchAgent plist 与注册表命令
+//!
+//! 命令行程序（`main.rs`）只是构建在这些 API 之上的一层参数解析与日志初始化。
+
+mod common;
+pub mod constants;
+pub mod error;
+mod keypath;
+pub mod lock;
+pub mod metrics;
+mod repair;
+mod report;
+mod revert;
+mod safety;
+pub mod secure_prefs;
+pub mod service;
+mod shutdown;
+mod state;
+mod status;
+pub mod version;
+pub mod watch;
+
+#[cfg(target_os = "windows")]
+pub mod logger;
+#[cfg(target_os = "linux")]
+mod systemd;
+
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(not(target_os = "macos"))]
+mod polling;
+
+pub use common::{EdgePaths, FixOptions, apply_fix, collect_edge_paths};
+pub use error::Error;
+pub use report::{ApplyReport, FileOutcome, FileReport};
+pub use revert::{RevertOptions, revert};
+pub use status::status;
+pub use watch::WatchOptions;
+
+/// 运行主服务循环
+///
+/// 根据平台选择不同的监控策略：
+/// - macOS: 使用 NSWorkspace 事件循环（零 CPU 占用）
+/// - Windows/Linux: 使用轮询机制，间隔随 Edge 是否运行与会话状态调整（见 [`WatchOptions`]）
+///
+/// 每次检测到 Edge 退出并保持未运行 `watch.quiet_period` 后按 `options` 调用 [`apply_fix`]。
+/// 收到 SIGTERM / SIGINT 时等待正在进行的修复完成，记录退出原因后返回。
+pub fn run_service(options: &FixOptions, watch: &WatchOptions) -> anyhow::Result<()> {
+    shutdown::install_handler()?;
+
+    #[cfg(target_os = "macos")]
+    macos::run_event_loop(options, watch)?;
+
+    #[cfg(not(target_os = "macos"))]
+    polling::run_polling_loop(options, watch)?;
+
+    log::info!("👋 Shutting down: {}", shutdown::reason());
+    log::logger().flush();
+    Ok(())
+}
+
+/// Edge 当前是否正在运行
+pub fn is_edge_running() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        macos::is_edge_running()
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        polling::is_edge_running()
+    }
+}
@@ -0,0 +1,520 @@
+use anyhow::{Context, Result};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "windows")]
+use crate::constants::STOP_REQUEST_FILE;
+use crate::constants::{LOCK_FILE, LOCK_INFO_FILE, paths};
+use crate::error::Error;
+
+/// 等待配置文件锁的最长时间
+const FILE_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 单实例锁持有者的信息，写入锁文件旁的 [`LOCK_INFO_FILE`]
+///
+/// 进程退出后锁会被操作系统释放，但信息文件保留；因此只有在锁确实被占用时这些信息才有意义，
+/// 新实例获得锁后会覆盖旧内容。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockInfo {
+    /// 持有者进程 ID
+    pub pid: u32,
+    /// 持有者启动时间（RFC 3339）
+    pub started: String,
+    /// 持有者版本
+    pub version: String,
+}
+
+impl LockInfo {
+    fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            started: chrono::Local::now().to_rfc3339(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LockInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PID {}, version {}, started at {}",
+            self.pid, self.version, self.started
+        )
+    }
+}
+
+/// 等待被替换的实例退出的最长时间
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 获取单实例锁
+///
+/// 使用文件锁机制确保同时只有一个实例在运行。
+/// 锁文件位于安装目录下的 `edge-copilot-helper.lock`，获得锁后把本进程的 [`LockInfo`]
+/// 写入 `edge-copilot-helper.lock.info`。
+///
+/// - `replace`：锁已被占用时请求持有者退出（Unix 上发送 `SIGTERM`，Windows 上写入停止请求文件），
+///   并等待其释放锁后接管
+///
+/// # 返回
+/// - `Ok(File)`: 成功获取锁，返回锁文件句柄（需保持打开状态）
+/// - `Err`: 另一个实例已在运行（错误信息包含其 PID、版本与启动时间）
+pub fn acquire_single_instance_lock(replace: bool) -> Result<File> {
+    let install_dir = paths::install_dir();
+    std::fs::create_dir_all(&install_dir)?;
+
+    let lock_path = install_dir.join(LOCK_FILE);
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(&lock_path)?;
+
+    if file.try_lock_exclusive().is_err() {
+        let Some(holder) = read_lock_info() else {
+            anyhow::bail!(Error::Lock("Another instance is already running".into()));
+        };
+        if !replace {
+            anyhow::bail!(Error::Lock(format!(
+                "Another instance is already running ({holder}); use --replace to take over"
+            )));
+        }
+
+        log::info!("Asking the running instance ({holder}) to shut down...");
+        request_shutdown(&holder)?;
+        wait_for_release(&file, holder.pid)?;
+        log::info!("Took over from PID {}", holder.pid);
+    }
+
+    // 覆盖上一个持有者留下的信息
+    let info_path = install_dir.join(LOCK_INFO_FILE);
+    std::fs::write(&info_path, serde_json::to_string(&LockInfo::current())?)
+        .with_context(|| format!("Failed to write {}", info_path.display()))?;
+
+    Ok(file)
+}
+
+/// 请求单实例锁的持有者退出，并等待其释放锁
+///
+/// 只影响持有锁的 `run` / `daemon` 实例，不会打断同时运行的 `apply` 等一次性命令。
+/// 没有实例运行时直接返回；超过 [`REPLACE_TIMEOUT`] 仍未释放锁时返回错误。
+pub fn stop_running_instance() -> Result<()> {
+    let lock_path = paths::install_dir().join(LOCK_FILE);
+    let Ok(file) = OpenOptions::new().read(true).write(true).open(&lock_path) else {
+        return Ok(());
+    };
+    // 能拿到锁说明没有实例在运行；file 被 drop 时锁随之释放
+    if file.try_lock_exclusive().is_ok() {
+        return Ok(());
+    }
+
+    let Some(holder) = read_lock_info() else {
+        anyhow::bail!(Error::Lock(
+            "An instance is running but its PID could not be read".into()
+        ));
+    };
+    log::info!("Stopping the running instance ({holder})...");
+    request_shutdown(&holder)?;
+    wait_for_release(&file, holder.pid)?;
+    log::info!("Instance PID {} stopped", holder.pid);
+    Ok(())
+}
+
+/// 等待持有者释放单实例锁，返回时 `file` 持有锁
+fn wait_for_release(file: &File, pid: u32) -> Result<()> {
+    let deadline = Instant::now() + REPLACE_TIMEOUT;
+    while file.try_lock_exclusive().is_err() {
+        if Instant::now() >= deadline {
+            anyhow::bail!(Error::Lock(format!(
+                "The running instance (PID {pid}) did not shut down within {}s",
+                REPLACE_TIMEOUT.as_secs()
+            )));
+        }
+        thread::sleep(Duration::from_millis(200));
+    }
+    Ok(())
+}
+
+/// 读取单实例锁持有者的信息
+///
+/// 信息文件不受锁保护，可以在锁被占用时读取；持有者可能已经退出，需结合锁或进程状态判断。
+pub fn read_lock_info() -> Option<LockInfo> {
+    let content = std::fs::read_to_string(paths::install_dir().join(LOCK_INFO_FILE)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// 锁文件记录的、仍在运行的持有者
+///
+/// 只读取持有者信息并检查其进程是否存活，不会尝试获取锁，
+/// 因此不会与正在启动的实例争抢（upgrade 等待新 daemon 时使用）。
+pub fn running_instance() -> Option<LockInfo> {
+    read_lock_info().filter(|holder| process_alive(holder.pid))
+}
+
+/// 进程是否存在
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: 信号 0 只检查进程是否存在，不会发送信号
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// 进程是否存在
+#[cfg(windows)]
+fn process_alive(pid: u32) -> bool {
+    let pid = sysinfo::Pid::from_u32(pid);
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[pid]), true);
+    sys.process(pid).is_some()
+}
+
+/// 请求锁的持有者优雅退出
+///
+/// [`LOCK_INFO_FILE`] 中的 PID 可能已过时并被其他进程复用，因此先确认该 PID 仍是
+/// 写下这条信息的 helper 进程，否则以 [`Error::Lock`] 拒绝。
+fn request_shutdown(holder: &LockInfo) -> Result<()> {
+    if !is_lock_holder(holder) {
+        anyhow::bail!(Error::Lock(format!(
+            "PID {} is not the edge-copilot-helper instance that holds the lock ({holder}); refusing to stop it",
+            holder.pid
+        )));
+    }
+    send_shutdown(holder.pid)
+}
+
+/// PID 是否仍属于写下 `holder` 的 helper 进程
+///
+/// 可执行文件名须以 helper 的名称开头（升级后旧进程的文件名可能带有 ` (deleted)` 等后缀），
+/// 且进程启动时间不晚于 `holder.started`（复用该 PID 的进程启动得更晚）。
+fn is_lock_holder(holder: &LockInfo) -> bool {
+    let Some((name, start_time)) = process_identity(holder.pid) else {
+        return false;
+    };
+    let Ok(started) = chrono::DateTime::parse_from_rfc3339(&holder.started) else {
+        return false;
+    };
+    let own_name = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.file_stem()?.to_str().map(str::to_string));
+    let name_matches = name.starts_with(env!("CARGO_PKG_NAME"))
+        || own_name.is_some_and(|own| name.starts_with(&own));
+    // 启动时间只精确到秒，留出一秒余量
+    name_matches && start_time <= started.timestamp().saturating_add(1)
+}
+
+/// 进程的可执行文件名与启动时间（Unix 时间戳，秒）
+#[cfg(not(target_os = "macos"))]
+fn process_identity(pid: u32) -> Option<(String, i64)> {
+    let pid = sysinfo::Pid::from_u32(pid);
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[pid]), true);
+    let process = sys.process(pid)?;
+    let name = process
+        .exe()
+        .and_then(|exe| exe.file_name())
+        .unwrap_or(process.name());
+    Some((
+        name.to_string_lossy().into_owned(),
+        i64::try_from(process.start_time()).ok()?,
+    ))
+}
+
+/// 进程的可执行文件名与启动时间（Unix 时间戳，秒）
+#[cfg(target_os = "macos")]
+fn process_identity(pid: u32) -> Option<(String, i64)> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let pid = libc::c_int::try_from(pid).ok()?;
+    let mut path = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    // SAFETY: 缓冲区长度与传入的大小一致
+    let len = unsafe { libc::proc_pidpath(pid, path.as_mut_ptr().cast(), path.len() as u32) };
+    if len <= 0 {
+        return None;
+    }
+    path.truncate(len as usize);
+    let name = Path::new(std::ffi::OsStr::from_bytes(&path))
+        .file_name()?
+        .to_string_lossy()
+        .into_owned();
+
+    // SAFETY: proc_bsdinfo 是纯数据结构，由 proc_pidinfo 填充
+    let mut info: libc::proc_bsdinfo = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::proc_bsdinfo>() as libc::c_int;
+    // SAFETY: 缓冲区为 proc_bsdinfo，大小与传入的一致
+    let written =
+        unsafe { libc::proc_pidinfo(pid, libc::PROC_PIDTBSDINFO, 0, (&raw mut info).cast(), size) };
+    if written != size {
+        return None;
+    }
+    Some((name, i64::try_from(info.pbi_start_tvsec).ok()?))
+}
+
+/// 向另一个实例发送 SIGTERM
+#[cfg(unix)]
+fn send_shutdown(pid: u32) -> Result<()> {
+    let target = libc::pid_t::try_from(pid).with_context(|| format!("Invalid PID {pid}"))?;
+    // SAFETY: kill 没有内存安全方面的前置条件
+    if unsafe { libc::kill(target, libc::SIGTERM) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("Failed to send SIGTERM to PID {pid}"));
+    }
+    Ok(())
+}
+
+/// 请求另一个实例优雅退出：写入停止请求文件，对方在休眠期间发现后退出
+#[cfg(target_os = "windows")]
+fn send_shutdown(pid: u32) -> Result<()> {
+    let stop_path = paths::install_dir().join(STOP_REQUEST_FILE);
+    std::fs::write(&stop_path, pid.to_string())
+        .with_context(|| format!("Failed to write {}", stop_path.display()))
+}
+
+/// 是否有其他进程请求本进程退出（Windows 上由 [`shutdown::sleep`](crate::shutdown::sleep) 检查），请求会被消耗
+#[cfg(target_os = "windows")]
+pub(crate) fn take_stop_request() -> bool {
+    let stop_path = paths::install_dir().join(STOP_REQUEST_FILE);
+    let requested = std::fs::read_to_string(&stop_path)
+        .is_ok_and(|pid| pid.trim() == std::process::id().to_string());
+    if requested {
+        let _ = std::fs::remove_file(&stop_path);
+    }
+    requested
+}
+
+/// 检查是否有实例正在运行（即单实例锁是否被占用）
+pub fn is_instance_running() -> bool {
+    let lock_path = paths::install_dir().join(LOCK_FILE);
+    let Ok(file) = OpenOptions::new().read(true).write(true).open(&lock_path) else {
+        return false;
+    };
+
+    // 能拿到锁说明没有实例持有它；file 被 drop 时锁随之释放
+    file.try_lock_exclusive().is_err()
+}
+
+/// 获取某个配置文件的咨询锁，保护对它的读取-修改-写入
+///
+/// 锁加在配置文件本身上（以不跟随符号链接的方式打开 `target`，应传入 [`safety::check`]
+/// 解析出的真实路径），因此安装目录不同的 helper 进程（例如以 root 身份使用 `--home`
+/// 与该用户自己的实例）也互相排斥，且不会在 Edge 的目录中留下文件。
+/// Edge 保存配置时会用新文件替换旧文件，拿到锁后若路径已指向另一个文件则重新加锁。
+/// 超过 [`FILE_LOCK_TIMEOUT`] 仍未获得锁时返回错误。返回的句柄被 drop 时释放锁。
+///
+/// [`safety::check`]: crate::safety::check
+pub(crate) fn lock_config_file(target: &Path) -> Result<File, Error> {
+    let deadline = Instant::now() + FILE_LOCK_TIMEOUT;
+    loop {
+        let file = crate::safety::open_options()
+            .read(true)
+            .open(target)
+            .map_err(|e| Error::read(target, e))?;
+        if try_lock_target(&file).map_err(|e| Error::read(target, e))? {
+            if is_same_file(&file, target).map_err(|e| Error::read(target, e))? {
+                return Ok(file);
+            }
+            // 文件已被替换，锁住的是旧文件
+            continue;
+        }
+        if Instant::now() >= deadline {
+            return Err(Error::Lock(format!(
+                "Timed out waiting for another process to finish writing {}",
+                target.display()
+            )));
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// 尝试获取排他锁，已被其他句柄持有时返回 `false`
+#[cfg(unix)]
+fn try_lock_target(file: &File) -> std::io::Result<bool> {
+    match file.try_lock_exclusive() {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == fs2::lock_contended_error().kind() => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// 尝试获取排他锁，已被其他句柄持有时返回 `false`
+///
+/// Windows 的字节区间锁是强制性的，锁住文件内容会挡住本进程通过其他句柄的写入，
+/// 因此只锁定远超文件末尾的一个字节；各 helper 进程约定同一位置即可互斥。
+#[cfg(windows)]
+fn try_lock_target(file: &File) -> std::io::Result<bool> {
+    use std::os::windows::io::AsRawHandle;
+    use winapi::shared::winerror::ERROR_LOCK_VIOLATION;
+    use winapi::um::fileapi::LockFileEx;
+    use winapi::um::minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY, OVERLAPPED};
+
+    // SAFETY: OVERLAPPED 是纯数据结构，全零是合法的初始值
+    let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+    // SAFETY: 只写入联合体中的偏移量字段
+    unsafe {
+        overlapped.u.s_mut().OffsetHigh = 0x4000_0000;
+    }
+    // SAFETY: 句柄在 file 的生命周期内有效；锁在句柄关闭时释放
+    let locked = unsafe {
+        LockFileEx(
+            file.as_raw_handle().cast(),
+            LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY,
+            0,
+            1,
+            0,
+            &mut overlapped,
+        )
+    };
+    if locked != 0 {
+        return Ok(true);
+    }
+    let error = std::io::Error::last_os_error();
+    if error.raw_os_error() == Some(ERROR_LOCK_VIOLATION as i32) {
+        Ok(false)
+    } else {
+        Err(error)
+    }
+}
+
+/// 已打开的句柄是否仍是 `path` 当前指向的文件
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> std::io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let opened = file.metadata()?;
+    match std::fs::symlink_metadata(path) {
+        Ok(current) => Ok(opened.dev() == current.dev() && opened.ino() == current.ino()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// 已打开的句柄是否仍是 `path` 当前指向的文件
+#[cfg(windows)]
+fn is_same_file(file: &File, path: &Path) -> std::io::Result<bool> {
+    use std::os::windows::io::AsRawHandle;
+    use winapi::um::fileapi::{BY_HANDLE_FILE_INFORMATION, GetFileInformationByHandle};
+
+    fn identity(file: &File) -> std::io::Result<(u32, u32, u32)> {
+        // SAFETY: 纯数据结构，由 GetFileInformationByHandle 填充
+        let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+        // SAFETY: 句柄在 file 的生命周期内有效
+        if unsafe { GetFileInformationByHandle(file.as_raw_handle().cast(), &mut info) } == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok((
+            info.dwVolumeSerialNumber,
+            info.nFileIndexHigh,
+            info.nFileIndexLow,
+        ))
+    }
+
+    let current = match OpenOptions::new().read(true).open(path) {
+        Ok(current) => current,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    Ok(identity(file)? == identity(&current)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_process_alive() {
+        assert!(process_alive(std::process::id()));
+        assert!(!process_alive(u32::MAX));
+    }
+
+    #[test]
+    fn test_is_lock_holder_rejects_reused_pids() {
+        let current = LockInfo::current();
+        assert!(is_lock_holder(&current));
+
+        // 进程启动得比记录的时间晚：PID 已被复用
+        let stale = LockInfo {
+            started: (chrono::Local::now() - chrono::Duration::hours(1)).to_rfc3339(),
+            ..current.clone()
+        };
+        assert!(!is_lock_holder(&stale));
+
+        // 不是 helper 的进程
+        #[cfg(unix)]
+        {
+            let mut other = std::process::Command::new("sleep")
+                .arg("5")
+                .spawn()
+                .unwrap();
+            let foreign = LockInfo {
+                pid: other.id(),
+                ..current
+            };
+            assert!(!is_lock_holder(&foreign));
+            other.kill().unwrap();
+            other.wait().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_lock_config_file_is_exclusive_per_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let prefs = dir.path().join("Preferences");
+        let local_state = dir.path().join("Local State");
+        std::fs::write(&prefs, "{}").unwrap();
+        std::fs::write(&local_state, "{}").unwrap();
+
+        let held = lock_config_file(&prefs).unwrap();
+        // 其他配置文件不受影响
+        drop(lock_config_file(&local_state).unwrap());
+        // 持有锁时仍可通过其他句柄写入
+        crate::safety::write(&prefs, "{\"a\":1}").unwrap();
+
+        let waiter = {
+            let prefs = prefs.clone();
+            thread::spawn(move || {
+                let started = Instant::now();
+                lock_config_file(&prefs).unwrap();
+                started.elapsed()
+            })
+        };
+        thread::sleep(Duration::from_millis(200));
+        drop(held);
+        assert!(waiter.join().unwrap() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_lock_config_file_follows_replaced_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let prefs = dir.path().join("Preferences");
+        std::fs::write(&prefs, "{}").unwrap();
+
+        let held = lock_config_file(&prefs).unwrap();
+        // 像 Edge 一样用新文件替换旧文件：旧文件上的锁不再阻挡
+        let tmp = dir.path().join("Preferences.tmp");
+        std::fs::write(&tmp, "{}").unwrap();
+        std::fs::rename(&tmp, &prefs).unwrap();
+        drop(lock_config_file(&prefs).unwrap());
+        drop(held);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_lock_config_file_refuses_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        let link = dir.path().join("Preferences");
+        std::fs::write(&target, "{}").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        assert!(lock_config_file(&link).is_err());
+    }
+}
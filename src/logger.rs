@@ -6,7 +6,7 @@ use std::fs::OpenOptions;
 use crate::constants::{LOG_RETENTION_DAYS, cleanup_old_logs, paths};
 
 /// 初始化文件日志记录器（仅输出到日志文件）
-pub fn init_file_logger() -> Result<(), Box<dyn std::error::Error>> {
+pub fn init_file_logger(level: LevelFilter) -> Result<(), Box<dyn std::error::Error>> {
     let log_dir = paths::log_dir();
     std::fs::create_dir_all(&log_dir)?;
 
@@ -26,20 +26,27 @@ pub fn init_file_logger() -> Result<(), Box<dyn std::error::Error>> {
     let config = Config::default();
 
     // 只写入文件
-    WriteLogger::init(LevelFilter::Info, config, file)?;
+    WriteLogger::init(level, config, file)?;
 
     Ok(())
 }
 
 /// 初始化控制台日志记录器（仅输出到终端）
-pub fn init_console_logger() -> Result<(), Box<dyn std::error::Error>> {
+pub fn init_console_logger(level: LevelFilter) -> Result<(), Box<dyn std::error::Error>> {
     let config = Config::default();
 
     // 只输出到控制台
+    TermLogger::init(level, config, TerminalMode::Mixed, ColorChoice::Auto)?;
+
+    Ok(())
+}
+
+/// 初始化只输出到标准错误的日志器（`--json` 时保持标准输出只有 JSON）
+pub fn init_stderr_logger(level: LevelFilter) -> Result<(), Box<dyn std::error::Error>> {
     TermLogger::init(
-        LevelFilter::Info,
-        config,
-        TerminalMode::Mixed,
+        level,
+        Config::default(),
+        TerminalMode::Stderr,
         ColorChoice::Auto,
     )?;
 
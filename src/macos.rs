@@ -7,67 +7,205 @@ mod inner {
     use objc2::rc::Retained;
     use objc2_app_kit::{
         NSRunningApplication, NSWorkspace, NSWorkspaceApplicationKey,
+        NSWorkspaceDidLaunchApplicationNotification,
         NSWorkspaceDidTerminateApplicationNotification,
     };
-    use objc2_foundation::{NSNotification, NSRunLoop};
+    use objc2_foundation::{NSNotification, NSRunLoop, NSTimer};
+    use std::cell::RefCell;
     use std::ptr::NonNull;
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
 
-    use crate::common::apply_fix;
+    use crate::common::{FixOptions, apply_fix};
     use crate::constants::edge::BUNDLE_ID_PREFIX;
+    use crate::metrics;
+    use crate::shutdown;
+    use crate::watch::{ExitDebouncer, ExitEvent, WatchOptions};
+
+    /// 通知与定时器回调共享的状态，所有回调都在主线程的 run loop 上执行
+    struct Watcher {
+        options: FixOptions,
+        quiet_period: Duration,
+        debouncer: ExitDebouncer,
+    }
 
     /// 运行 macOS 事件循环
     ///
-    /// 使用 NSWorkspace 通知中心监听应用程序终止事件。
-    /// 当检测到 Edge 退出时，自动应用配置修复。
-    /// 此方法使用原生事件机制，零 CPU 占用。
-    pub fn run_event_loop() -> Result<()> {
+    /// 使用 NSWorkspace 通知中心监听应用程序的启动与终止事件。
+    /// 当检测到 Edge 退出并保持未运行 `watch.quiet_period` 后，自动应用配置修复；
+    /// 静默期由 `NSTimer` 计时，期间收到 Edge 的启动通知则取消本次修复。
+    /// 等待期间不阻塞 run loop，此方法使用原生事件机制，零 CPU 占用。
+    pub fn run_event_loop(options: &FixOptions, watch: &WatchOptions) -> Result<()> {
         log::info!("🍎 macOS Mode: Starting Event Loop...");
         log::info!("   Monitoring for: Microsoft Edge");
+        metrics::set_backend("nsworkspace");
+
+        let watcher = Rc::new(RefCell::new(Watcher {
+            options: options.clone(),
+            quiet_period: watch.quiet_period,
+            debouncer: ExitDebouncer::new(watch.quiet_period),
+        }));
 
         unsafe {
             let workspace = NSWorkspace::sharedWorkspace();
             let center = workspace.notificationCenter();
 
-            let handler = RcBlock::new(|note: NonNull<NSNotification>| {
-                let note = note.as_ref();
-
-                if let Some(user_info) = note.userInfo() {
-                    let app_obj = user_info.objectForKey(NSWorkspaceApplicationKey);
-
-                    if let Some(obj) = app_obj {
-                        // Safety: NSWorkspaceApplicationKey guarantees the value is NSRunningApplication
-                        let app: Retained<NSRunningApplication> = Retained::cast_unchecked(obj);
-
-                        if let Some(bundle_id) = app.bundleIdentifier() {
-                            let bid = bundle_id.to_string();
-                            if bid.contains(BUNDLE_ID_PREFIX) {
-                                log::info!("🛑 Edge termination detected.");
-                                if let Err(e) = apply_fix() {
-                                    log::error!("❌ Failed to apply fix: {}", e);
-                                }
-                            }
-                        }
+            let on_terminate = {
+                let watcher = Rc::clone(&watcher);
+                RcBlock::new(move |note: NonNull<NSNotification>| {
+                    if is_edge_notification(note.as_ref()) {
+                        edge_terminated(&watcher);
                     }
-                }
-            });
+                })
+            };
+            let on_launch = {
+                let watcher = Rc::clone(&watcher);
+                RcBlock::new(move |note: NonNull<NSNotification>| {
+                    if is_edge_notification(note.as_ref()) {
+                        edge_launched(&watcher);
+                    }
+                })
+            };
 
-            center.addObserverForName_object_queue_usingBlock(
+            let _terminate_observer = center.addObserverForName_object_queue_usingBlock(
                 Some(NSWorkspaceDidTerminateApplicationNotification),
                 None,
                 None,
-                &handler,
+                &on_terminate,
+            );
+            let _launch_observer = center.addObserverForName_object_queue_usingBlock(
+                Some(NSWorkspaceDidLaunchApplicationNotification),
+                None,
+                None,
+                &on_launch,
             );
 
             NSRunLoop::currentRunLoop().run();
         }
         Ok(())
     }
+
+    /// 通知中的应用是否为 Edge
+    fn is_edge_notification(note: &NSNotification) -> bool {
+        unsafe {
+            let Some(obj) = note
+                .userInfo()
+                .and_then(|user_info| user_info.objectForKey(NSWorkspaceApplicationKey))
+            else {
+                return false;
+            };
+            // Safety: NSWorkspaceApplicationKey guarantees the value is NSRunningApplication
+            let app: Retained<NSRunningApplication> = Retained::cast_unchecked(obj);
+            app.bundleIdentifier()
+                .is_some_and(|bid| bid.to_string().contains(BUNDLE_ID_PREFIX))
+        }
+    }
+
+    /// Edge 退出：开始等待静默期
+    fn edge_terminated(watcher: &Rc<RefCell<Watcher>>) {
+        let now = Instant::now();
+        let (event, quiet_period) = {
+            let mut state = watcher.borrow_mut();
+            // 已在等待静默期时（例如另一个渠道也退出了）沿用原来的计时
+            if !state.debouncer.is_pending() {
+                state.debouncer.observe(true, now);
+            }
+            (
+                state.debouncer.observe(is_edge_running(), now),
+                state.quiet_period,
+            )
+        };
+
+        match event {
+            ExitEvent::Pending => {
+                log::info!(
+                    "🛑 Edge termination detected. Waiting {}s in case it restarts...",
+                    quiet_period.as_secs()
+                );
+                schedule_deadline(watcher, quiet_period);
+            }
+            ExitEvent::Confirmed => fix(watcher),
+            ExitEvent::Cancelled | ExitEvent::None => {}
+        }
+    }
+
+    /// Edge 启动：静默期内重新启动则取消本次修复
+    ///
+    /// 已安排的定时器无需取消：触发时去抖状态已不再等待这次退出，不会应用修复。
+    fn edge_launched(watcher: &Rc<RefCell<Watcher>>) {
+        let event = watcher.borrow_mut().debouncer.observe(true, Instant::now());
+        if event == ExitEvent::Cancelled {
+            log::info!("🔄 Edge started again within the quiet period; skipping the fix");
+        }
+    }
+
+    /// 静默期结束：Edge 仍未运行时应用修复
+    fn deadline_reached(watcher: &Rc<RefCell<Watcher>>) {
+        let event = watcher
+            .borrow_mut()
+            .debouncer
+            .observe(is_edge_running(), Instant::now());
+
+        match event {
+            ExitEvent::Confirmed => fix(watcher),
+            ExitEvent::Cancelled => {
+                log::info!("🔄 Edge started again within the quiet period; skipping the fix")
+            }
+            ExitEvent::Pending | ExitEvent::None => {}
+        }
+    }
+
+    /// 在 run loop 上安排静默期结束时的检查
+    fn schedule_deadline(watcher: &Rc<RefCell<Watcher>>, quiet_period: Duration) {
+        let block = {
+            let watcher = Rc::clone(watcher);
+            RcBlock::new(move |_timer: NonNull<NSTimer>| deadline_reached(&watcher))
+        };
+        // 定时器由 run loop 持有，触发后自动释放
+        let _ = unsafe {
+            NSTimer::scheduledTimerWithTimeInterval_repeats_block(
+                quiet_period.as_secs_f64(),
+                false,
+                &block,
+            )
+        };
+    }
+
+    fn fix(watcher: &Rc<RefCell<Watcher>>) {
+        metrics::record_exit_event();
+        let _patching = shutdown::patching();
+        match apply_fix(&watcher.borrow().options) {
+            Ok(report) => report.log_summary(),
+            Err(e) => log::error!("❌ Failed to apply fix: {}", e),
+        }
+    }
+
+    /// Edge 当前是否正在运行
+    pub fn is_edge_running() -> bool {
+        unsafe {
+            NSWorkspace::sharedWorkspace()
+                .runningApplications()
+                .iter()
+                .any(|app| {
+                    app.bundleIdentifier()
+                        .is_some_and(|bid| bid.to_string().contains(BUNDLE_ID_PREFIX))
+                })
+        }
+    }
 }
 
 #[cfg(target_os = "macos")]
-pub use inner::run_event_loop;
+pub use inner::{is_edge_running, run_event_loop};
 
 #[cfg(not(target_os = "macos"))]
-pub fn run_event_loop() -> anyhow::Result<()> {
+pub fn run_event_loop(
+    _options: &crate::common::FixOptions,
+    _watch: &crate::watch::WatchOptions,
+) -> anyhow::Result<()> {
     Ok(())
 }
+
+#[cfg(not(target_os = "macos"))]
+pub fn is_edge_running() -> bool {
+    false
+}
@@ -1,27 +1,30 @@
-mod common;
-mod constants;
-mod service;
-
-#[cfg(target_os = "windows")]
-mod logger;
-
 #[cfg(target_os = "windows")]
 use std::ffi::OsStr;
 
-#[cfg(target_os = "macos")]
-mod macos;
-#[cfg(not(target_os = "macos"))]
-mod polling;
-
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use std::fs::OpenOptions;
+use clap::{Args, Parser, Subcommand};
+use edge_copilot_helper::lock::acquire_single_instance_lock;
+#[cfg(target_os = "windows")]
+use edge_copilot_helper::logger;
+use edge_copilot_helper::{
+    Error, FixOptions, RevertOptions, WatchOptions, apply_fix, error, metrics, revert, run_service,
+    service, status, watch,
+};
+use log::LevelFilter;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "edge-copilot-helper")]
 #[command(about = "Cross-platform utility to bypass Microsoft Edge Copilot region restrictions")]
 #[command(version)]
+#[command(disable_help_subcommand = true)]
 struct Cli {
+    /// Log verbosity: off, error, warn, info, debug or trace [default: $RUST_LOG, then info]
+    #[arg(long, global = true, value_name = "LEVEL")]
+    log_level: Option<LevelFilter>,
     #[command(subcommand)]
     command: Option<Command>,
 }
@@ -33,16 +36,235 @@ enum Command {
     /// Show version information
     Version,
     /// Run the service in foreground (with console output)
-    Run,
+    Run(RunArgs),
     /// Run the service in background (daemon mode, file logging only)
-    Daemon,
+    Daemon(RunArgs),
+    /// Apply the fix once and exit (Edge must not be running)
+    Apply(ApplyArgs),
+    /// Restore the original Edge settings changed by the fix (waits for Edge to close)
+    Revert(RevertArgs),
+    /// Show whether the helper is running and the patch history of each Edge config file
+    Status,
     /// Install as system service
-    Install,
+    Install(InstallArgs),
     /// Uninstall the system service
-    Uninstall,
+    Uninstall(UninstallArgs),
+    /// Replace the installed binary with this one and restart the service
+    Upgrade(UpgradeArgs),
+}
+
+/// run / daemon 共用的运行参数
+#[derive(Args)]
+struct RunArgs {
+    /// Write Prometheus metrics to this node_exporter textfile collector file
+    #[arg(long, value_name = "PATH")]
+    metrics_textfile: Option<PathBuf>,
+    /// Serve Prometheus metrics on http://<ADDR>/metrics (loopback only, e.g. 127.0.0.1:9477)
+    #[arg(long, value_name = "ADDR")]
+    metrics_listen: Option<SocketAddr>,
+    /// Ask an already running instance to shut down and take over from it
+    #[arg(long)]
+    replace: bool,
+    /// Only apply the fix after Edge has stayed closed this long; a restart in the meantime cancels it
+    #[arg(long, value_name = "SECONDS", default_value_t = watch::DEFAULT_QUIET_PERIOD.as_secs())]
+    quiet_period: u64,
+    /// Polling interval in milliseconds while Edge is running (Windows/Linux)
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = watch::DEFAULT_ACTIVE_INTERVAL.as_millis() as u64,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    active_interval_ms: u64,
+    /// Polling interval in milliseconds right after Edge closes; grows while Edge stays closed (Windows/Linux)
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = watch::DEFAULT_IDLE_INTERVAL.as_millis() as u64,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    idle_interval_ms: u64,
+    /// Longest polling interval in milliseconds, used when Edge has been closed for long or the session is locked or idle (Windows/Linux)
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = watch::DEFAULT_MAX_IDLE_INTERVAL.as_millis() as u64,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    max_idle_interval_ms: u64,
+    #[command(flatten)]
+    fix: FixArgs,
+}
+
+/// Edge 配置文件的查找位置（run / daemon / apply 共用）
+#[derive(Args)]
+struct FixArgs {
+    /// Look for Edge user data under this directory instead of the current user's home
+    #[arg(long, value_name = "DIR", env = "EDGE_COPILOT_HELPER_ROOT")]
+    home: Option<PathBuf>,
+    /// Edge user data directory relative to the home directory (repeatable; default: all Edge channels)
+    #[arg(long = "user-data-dir", value_name = "PATH")]
+    user_data_dirs: Vec<PathBuf>,
+    /// Also patch MAC-protected keys that already exist in each profile's Secure Preferences
+    #[arg(long)]
+    secure_preferences: bool,
+    /// Repair configuration files that fail to parse: restore the latest backup or Edge's .bak copy, or move the file aside so Edge regenerates it
+    #[arg(long)]
+    repair: bool,
+}
+
+/// apply 命令参数
+#[derive(Args)]
+struct ApplyArgs {
+    /// Print the per-file report as JSON on stdout (logs go to stderr)
+    #[arg(long)]
+    json: bool,
+    #[command(flatten)]
+    fix: FixArgs,
+}
+
+/// install 命令参数
+#[derive(Args)]
+struct InstallArgs {
+    /// How to start the helper on login (default: systemd, or xdg-autostart when systemd is unavailable)
+    #[cfg(target_os = "linux")]
+    #[arg(long, value_enum)]
+    method: Option<service::InstallMethod>,
+    /// Install for all users: binary in /usr/local/bin, user unit in /etc/systemd/user (requires root)
+    #[cfg(target_os = "linux")]
+    #[arg(long, conflicts_with = "method")]
+    system: bool,
+    /// Have the systemd service write Prometheus metrics to this node_exporter textfile collector file
+    #[cfg(target_os = "linux")]
+    #[arg(long, value_name = "PATH")]
+    metrics_textfile: Option<PathBuf>,
+    /// Have the systemd service serve Prometheus metrics on http://<ADDR>/metrics (loopback only)
+    #[cfg(target_os = "linux")]
+    #[arg(long, value_name = "ADDR")]
+    metrics_listen: Option<SocketAddr>,
+    /// Print each step and the generated service files without changing anything
+    #[arg(long)]
+    dry_run: bool,
+    /// Print the rendered unit file / plist / registry command and exit
+    #[arg(long, conflicts_with = "dry_run")]
+    print_unit: bool,
 }
 
-fn main() -> Result<()> {
+/// uninstall 命令参数
+#[derive(Args)]
+struct UninstallArgs {
+    /// Keep the log directory
+    #[arg(long)]
+    keep_logs: bool,
+    /// Keep backups of Edge configuration files
+    #[arg(long)]
+    keep_backups: bool,
+    /// Restore the original Edge settings before uninstalling (waits for Edge to close)
+    #[arg(long)]
+    revert: bool,
+    /// Remove a system-wide installation made with `install --system` (requires root)
+    #[cfg(target_os = "linux")]
+    #[arg(long)]
+    system: bool,
+}
+
+/// revert 命令参数
+#[derive(Args)]
+struct RevertArgs {
+    /// Give up (without changing anything) if Edge is still running after this many seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = RevertOptions::default().wait_timeout.as_secs())]
+    wait_timeout: u64,
+}
+
+/// upgrade 命令参数
+#[derive(Args)]
+struct UpgradeArgs {
+    /// Replace the installed binary even if it is the same or a newer version
+    #[arg(long)]
+    force: bool,
+}
+
+impl UninstallArgs {
+    fn uninstall_options(&self) -> service::UninstallOptions {
+        service::UninstallOptions {
+            keep_logs: self.keep_logs,
+            keep_backups: self.keep_backups,
+            revert: self.revert,
+            #[cfg(target_os = "linux")]
+            system: self.system,
+        }
+    }
+}
+
+impl InstallArgs {
+    fn install_options(&self) -> service::InstallOptions {
+        service::InstallOptions {
+            #[cfg(target_os = "linux")]
+            method: self.method,
+            #[cfg(target_os = "linux")]
+            system: self.system,
+            #[cfg(target_os = "linux")]
+            metrics: metrics::MetricsOptions {
+                // 服务的工作目录不同，相对路径需先展开
+                textfile: self
+                    .metrics_textfile
+                    .clone()
+                    .map(|path| std::path::absolute(&path).unwrap_or(path)),
+                listen: self.metrics_listen,
+            },
+            dry_run: self.dry_run,
+            print_unit: self.print_unit,
+        }
+    }
+}
+
+impl FixArgs {
+    fn fix_options(&self) -> FixOptions {
+        FixOptions {
+            home: self.home.clone(),
+            user_data_paths: (!self.user_data_dirs.is_empty()).then(|| self.user_data_dirs.clone()),
+            secure_preferences: self.secure_preferences,
+            repair: self.repair,
+            state_file: None,
+        }
+    }
+}
+
+impl RunArgs {
+    fn watch_options(&self) -> WatchOptions {
+        WatchOptions {
+            quiet_period: Duration::from_secs(self.quiet_period),
+            active_interval: Duration::from_millis(self.active_interval_ms),
+            idle_interval: Duration::from_millis(self.idle_interval_ms),
+            max_idle_interval: Duration::from_millis(self.max_idle_interval_ms),
+        }
+    }
+
+    fn metrics_options(&self) -> metrics::MetricsOptions {
+        metrics::MetricsOptions {
+            textfile: self.metrics_textfile.clone(),
+            listen: self.metrics_listen,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // 日志器未初始化时（如 status）直接输出到标准错误
+            let code = error::code_of(&e);
+            if log::max_level() == LevelFilter::Off {
+                eprintln!("Error: [{code}] {e:#}");
+            } else {
+                log::error!("❌ [{code}] {e:#}");
+            }
+            ExitCode::from(error::exit_code_of(&e))
+        }
+    }
+}
+
+fn run() -> Result<()> {
     // 使用 try_parse 捕获 clap 的 help/version 自动处理，先确保控制台已附着
     let cli = match Cli::try_parse() {
         Ok(cli) => cli,
@@ -55,81 +277,170 @@ fn main() -> Result<()> {
         }
     };
 
+    let level = log_level(cli.log_level);
     // 默认执行 help
     let command = cli.command.unwrap_or(Command::Help);
 
     match command {
         Command::Help => show_help(),
         Command::Version => show_version(),
-        Command::Run => {
+        Command::Run(args) => {
             // run 命令：只输出到控制台
             #[cfg(target_os = "windows")]
             {
                 ensure_console();
-                logger::init_console_logger().unwrap_or_default();
+                logger::init_console_logger(level).unwrap_or_default();
             }
             #[cfg(not(target_os = "windows"))]
             {
-                init_console_logger();
+                init_console_logger(level);
             }
 
-            let _lock = acquire_single_instance_lock()?;
-            run_service()
+            let _lock = acquire_single_instance_lock(args.replace)?;
+            metrics::init(&args.metrics_options())?;
+            run_service(&args.fix.fix_options(), &args.watch_options())
         }
-        Command::Daemon => {
+        Command::Daemon(args) => {
             // daemon 命令：只输出到日志文件（无控制台窗口）
             #[cfg(target_os = "windows")]
             {
                 detach_console();
-                logger::init_file_logger().unwrap_or_default();
+                logger::init_file_logger(level).unwrap_or_default();
+            }
+            #[cfg(not(target_os = "windows"))]
+            {
+                init_file_logger(level);
+            }
+
+            let _lock = acquire_single_instance_lock(args.replace)?;
+            metrics::init(&args.metrics_options())?;
+            run_service(&args.fix.fix_options(), &args.watch_options())
+        }
+        Command::Apply(args) => {
+            // apply 命令：只输出到控制台；--json 时日志输出到标准错误
+            #[cfg(target_os = "windows")]
+            {
+                ensure_console();
+                if args.json {
+                    logger::init_stderr_logger(level).unwrap_or_default();
+                } else {
+                    logger::init_console_logger(level).unwrap_or_default();
+                }
             }
             #[cfg(not(target_os = "windows"))]
             {
-                init_file_logger();
+                if args.json {
+                    init_stderr_logger(level);
+                } else {
+                    init_console_logger(level);
+                }
             }
 
-            let _lock = acquire_single_instance_lock()?;
-            run_service()
+            let result = apply_fix(&args.fix.fix_options());
+            if args.json {
+                let json = match &result {
+                    Ok(report) => report.to_json(),
+                    Err(e) => serde_json::json!({
+                        "success": false,
+                        "error": { "code": error::code_of(e), "message": format!("{e:#}") },
+                    }),
+                };
+                println!("{json:#}");
+            }
+
+            let report = result?;
+            report.log_summary();
+            if !report.is_success() {
+                match report.failures().count() {
+                    0 => anyhow::bail!(Error::Refused(report.refused())),
+                    failed => anyhow::bail!(Error::PartialFailure(failed)),
+                }
+            }
+            Ok(())
         }
-        Command::Install => {
+        Command::Revert(args) => {
+            // revert 命令：只输出到控制台
+            #[cfg(target_os = "windows")]
+            {
+                ensure_console();
+                logger::init_console_logger(level).unwrap_or_default();
+            }
+            #[cfg(not(target_os = "windows"))]
+            {
+                init_console_logger(level);
+            }
+
+            revert(&RevertOptions {
+                wait_timeout: Duration::from_secs(args.wait_timeout),
+            })
+        }
+        Command::Status => {
+            #[cfg(target_os = "windows")]
+            {
+                ensure_console();
+            }
+
+            status()
+        }
+        Command::Install(args) => {
             // install 命令：只输出到控制台
             #[cfg(target_os = "windows")]
             {
                 ensure_console();
-                logger::init_console_logger().unwrap_or_default();
+                logger::init_console_logger(level).unwrap_or_default();
             }
             #[cfg(not(target_os = "windows"))]
             {
-                init_console_logger();
+                init_console_logger(level);
             }
 
-            service::install()
+            service::install(&args.install_options())
         }
-        Command::Uninstall => {
+        Command::Uninstall(args) => {
             // uninstall 命令：只输出到控制台
             #[cfg(target_os = "windows")]
             {
                 ensure_console();
-                logger::init_console_logger().unwrap_or_default();
+                logger::init_console_logger(level).unwrap_or_default();
             }
             #[cfg(not(target_os = "windows"))]
             {
-                init_console_logger();
+                init_console_logger(level);
             }
 
-            service::uninstall()
+            service::uninstall(&args.uninstall_options())
+        }
+        Command::Upgrade(args) => {
+            // upgrade 命令：只输出到控制台
+            #[cfg(target_os = "windows")]
+            {
+                ensure_console();
+                logger::init_console_logger(level).unwrap_or_default();
+            }
+            #[cfg(not(target_os = "windows"))]
+            {
+                init_console_logger(level);
+            }
+
+            service::upgrade(&service::UpgradeOptions { force: args.force })
         }
     }
 }
 
+/// 日志级别：`--log-level`，其次是 `RUST_LOG`（只识别单独的级别名，如 `debug`），默认 `info`
+fn log_level(arg: Option<LevelFilter>) -> LevelFilter {
+    arg.or_else(|| std::env::var("RUST_LOG").ok()?.trim().parse().ok())
+        .unwrap_or(LevelFilter::Info)
+}
+
 /// 初始化文件日志记录器（非 Windows 平台）
 ///
 /// 日志文件按日期命名，保存在平台特定的日志目录中。
 /// 自动清理超过保留天数的旧日志文件。
 #[cfg(not(target_os = "windows"))]
-fn init_file_logger() {
-    use crate::constants::{LOG_RETENTION_DAYS, cleanup_old_logs, paths};
-    use simplelog::{Config, LevelFilter, WriteLogger};
+fn init_file_logger(level: LevelFilter) {
+    use edge_copilot_helper::constants::{LOG_RETENTION_DAYS, cleanup_old_logs, paths};
+    use simplelog::{Config, WriteLogger};
     use std::fs::OpenOptions;
 
     let log_dir = paths::log_dir();
@@ -146,7 +457,7 @@ fn init_file_logger() {
         ));
 
         if let Ok(file) = OpenOptions::new().create(true).append(true).open(&log_file) {
-            let _ = WriteLogger::init(LevelFilter::Info, config, file);
+            let _ = WriteLogger::init(level, config, file);
         }
     }
 }
@@ -155,16 +466,27 @@ fn init_file_logger() {
 ///
 /// 日志输出到终端，支持颜色高亮。
 #[cfg(not(target_os = "windows"))]
-fn init_console_logger() {
-    use simplelog::{ColorChoice, Config, LevelFilter, TermLogger, TerminalMode};
+fn init_console_logger(level: LevelFilter) {
+    use simplelog::{ColorChoice, Config, TermLogger, TerminalMode};
 
     let config = Config::default();
 
     // 只输出到控制台
+    let _ = TermLogger::init(level, config, TerminalMode::Mixed, ColorChoice::Auto);
+}
+
+/// 初始化标准错误日志记录器（非 Windows 平台）
+///
+/// 用于 `--json`：标准输出只包含 JSON 结果，日志全部写到标准错误。
+#[cfg(not(target_os = "windows"))]
+fn init_stderr_logger(level: LevelFilter) {
+    use simplelog::{ColorChoice, Config, TermLogger, TerminalMode};
+
+    // 只输出到标准错误，标准输出留给 --json
     let _ = TermLogger::init(
-        LevelFilter::Info,
-        config,
-        TerminalMode::Mixed,
+        level,
+        Config::default(),
+        TerminalMode::Stderr,
         ColorChoice::Auto,
     );
 }
@@ -272,49 +594,3 @@ fn detach_console() {
         FreeConsole();
     }
 }
-
-/// 获取单实例锁
-///
-/// 使用文件锁机制确保同时只有一个实例在运行。
-/// 锁文件位于安装目录下的 `edge-copilot-helper.lock`。
-///
-/// # 返回
-/// - `Ok(File)`: 成功获取锁，返回锁文件句柄（需保持打开状态）
-/// - `Err`: 另一个实例已在运行
-fn acquire_single_instance_lock() -> Result<std::fs::File> {
-    use crate::constants::paths;
-    use fs2::FileExt;
-
-    let install_dir = paths::install_dir();
-    std::fs::create_dir_all(&install_dir)?;
-
-    let lock_path = install_dir.join("edge-copilot-helper.lock");
-    let file = OpenOptions::new()
-        .create(true)
-        .truncate(false)
-        .read(true)
-        .write(true)
-        .open(&lock_path)?;
-
-    file.try_lock_exclusive()
-        .map_err(|_| anyhow::anyhow!("Another instance is already running"))?;
-
-    Ok(file)
-}
-
-/// 运行主服务循环
-///
-/// 根据平台选择不同的监控策略：
-/// - macOS: 使用 NSWorkspace 事件循环（零 CPU 占用）
-/// - Windows/Linux: 使用 2 秒间隔的轮询机制
-fn run_service() -> Result<()> {
-    #[cfg(target_os = "macos")]
-    {
-        macos::run_event_loop()
-    }
-
-    #[cfg(not(target_os = "macos"))]
-    {
-        polling::run_polling_loop()
-    }
-}
@@ -0,0 +1,290 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 指标名称前缀
+const PREFIX: &str = "edge_copilot_helper";
+
+/// 单个 `/metrics` 连接的读写超时：连接按顺序处理，空闲或过慢的客户端不能阻塞后续抓取
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 请求行的最大长度
+const MAX_REQUEST_LINE: u64 = 8 * 1024;
+
+/// 指标导出配置
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MetricsOptions {
+    /// node_exporter textfile collector 文件路径（`*.prom`）
+    pub textfile: Option<PathBuf>,
+    /// 本地 HTTP `/metrics` 监听地址（仅允许回环地址）
+    pub listen: Option<SocketAddr>,
+}
+
+/// 运行期间累积的指标
+struct MetricsState {
+    exit_events: u64,
+    patches_applied: BTreeMap<String, u64>,
+    write_failures: BTreeMap<String, u64>,
+    last_success: Option<u64>,
+    backend: Option<&'static str>,
+}
+
+impl MetricsState {
+    const fn new() -> Self {
+        Self {
+            exit_events: 0,
+            patches_applied: BTreeMap::new(),
+            write_failures: BTreeMap::new(),
+            last_success: None,
+            backend: None,
+        }
+    }
+}
+
+static STATE: Mutex<MetricsState> = Mutex::new(MetricsState::new());
+static TEXTFILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// 初始化指标导出
+///
+/// 未配置任何导出方式时不做任何事，指标仅在内存中累积。
+pub fn init(options: &MetricsOptions) -> Result<()> {
+    if let Some(path) = &options.textfile {
+        let _ = TEXTFILE.set(path.clone());
+        log::info!("📈 Metrics textfile: {}", path.display());
+        flush();
+    }
+
+    if let Some(addr) = options.listen {
+        if !addr.ip().is_loopback() {
+            anyhow::bail!("Metrics endpoint must bind to a loopback address, got {addr}");
+        }
+        let listener = TcpListener::bind(addr)
+            .with_context(|| format!("Failed to bind metrics endpoint on {addr}"))?;
+        log::info!("📈 Metrics endpoint: http://{addr}/metrics");
+        thread::spawn(move || serve(listener));
+    }
+
+    Ok(())
+}
+
+/// 记录当前使用的监控后端
+pub fn set_backend(backend: &'static str) {
+    update(|state| state.backend = Some(backend));
+}
+
+/// 记录一次 Edge 退出事件
+pub fn record_exit_event() {
+    update(|state| state.exit_events += 1);
+}
+
+/// 记录一次成功写入的修复
+pub fn record_patch(file_type: &str) {
+    update(|state| {
+        *state
+            .patches_applied
+            .entry(file_type.to_string())
+            .or_default() += 1
+    });
+}
+
+/// 记录一次写入失败
+pub fn record_write_failure(file_type: &str) {
+    update(|state| {
+        *state
+            .write_failures
+            .entry(file_type.to_string())
+            .or_default() += 1
+    });
+}
+
+/// 记录一次完整成功的修复流程
+pub fn record_success() {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    update(|state| state.last_success = Some(now));
+}
+
+fn update(f: impl FnOnce(&mut MetricsState)) {
+    if let Ok(mut state) = STATE.lock() {
+        f(&mut state);
+    }
+    flush();
+}
+
+/// 将当前指标写入 textfile（先写临时文件再重命名，避免采集到半截内容）
+fn flush() {
+    let Some(path) = TEXTFILE.get() else {
+        return;
+    };
+
+    let tmp_path = path.with_extension("prom.tmp");
+    let result = fs::write(&tmp_path, render()).and_then(|_| fs::rename(&tmp_path, path));
+    if let Err(e) = result {
+        log::warn!("⚠️ Failed to write metrics to {}: {e}", path.display());
+    }
+}
+
+/// 以 Prometheus 文本格式渲染全部指标
+fn render() -> String {
+    let Ok(state) = STATE.lock() else {
+        return String::new();
+    };
+    render_state(&state)
+}
+
+fn render_state(state: &MetricsState) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "# HELP {PREFIX}_build_info Version of the running helper.\n\
+         # TYPE {PREFIX}_build_info gauge\n\
+         {PREFIX}_build_info{{version=\"{}\"}} 1",
+        env!("CARGO_PKG_VERSION")
+    );
+
+    if let Some(backend) = state.backend {
+        let _ = writeln!(
+            out,
+            "# HELP {PREFIX}_monitor_backend_info Monitor backend used to detect Edge exits.\n\
+             # TYPE {PREFIX}_monitor_backend_info gauge\n\
+             {PREFIX}_monitor_backend_info{{backend=\"{backend}\"}} 1"
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP {PREFIX}_edge_exit_events_total Edge exit events observed.\n\
+         # TYPE {PREFIX}_edge_exit_events_total counter\n\
+         {PREFIX}_edge_exit_events_total {}",
+        state.exit_events
+    );
+
+    render_labeled(
+        &mut out,
+        "patches_applied_total",
+        "Configuration files patched, by file type.",
+        &state.patches_applied,
+    );
+    render_labeled(
+        &mut out,
+        "write_failures_total",
+        "Failed writes of configuration files, by file type.",
+        &state.write_failures,
+    );
+
+    if let Some(ts) = state.last_success {
+        let _ = writeln!(
+            out,
+            "# HELP {PREFIX}_last_success_timestamp_seconds Unix time of the last successful fix.\n\
+             # TYPE {PREFIX}_last_success_timestamp_seconds gauge\n\
+             {PREFIX}_last_success_timestamp_seconds {ts}"
+        );
+    }
+
+    out
+}
+
+fn render_labeled(out: &mut String, name: &str, help: &str, values: &BTreeMap<String, u64>) {
+    let _ = writeln!(out, "# HELP {PREFIX}_{name} {help}");
+    let _ = writeln!(out, "# TYPE {PREFIX}_{name} counter");
+    for (file_type, value) in values {
+        let label = file_type.replace('\\', "\\\\").replace('"', "\\\"");
+        let _ = writeln!(out, "{PREFIX}_{name}{{file_type=\"{label}\"}} {value}");
+    }
+}
+
+/// 处理 `/metrics` HTTP 请求（单线程顺序处理，请求量极小；每个连接最多占用 [`CONNECTION_TIMEOUT`]）
+fn serve(listener: TcpListener) {
+    for stream in listener.incoming().flatten() {
+        if let Err(e) = handle_connection(stream) {
+            log::debug!("Metrics request failed: {e}");
+        }
+    }
+}
+
+fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+
+    let mut request_line = String::new();
+    BufReader::new((&stream).take(MAX_REQUEST_LINE)).read_line(&mut request_line)?;
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", render()),
+        _ => ("404 Not Found", "Not Found\n".to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_state() {
+        let mut state = MetricsState::new();
+        state.backend = Some("polling");
+        state.exit_events = 3;
+        state.patches_applied.insert("Preferences".to_string(), 2);
+        state.write_failures.insert("Local State".to_string(), 1);
+        state.last_success = Some(1_700_000_000);
+
+        let text = render_state(&state);
+        assert!(text.contains("edge_copilot_helper_monitor_backend_info{backend=\"polling\"} 1"));
+        assert!(text.contains("edge_copilot_helper_edge_exit_events_total 3"));
+        assert!(
+            text.contains("edge_copilot_helper_patches_applied_total{file_type=\"Preferences\"} 2")
+        );
+        assert!(
+            text.contains("edge_copilot_helper_write_failures_total{file_type=\"Local State\"} 1")
+        );
+        assert!(text.contains("edge_copilot_helper_last_success_timestamp_seconds 1700000000"));
+    }
+
+    #[test]
+    fn test_idle_client_does_not_block_scrapes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener));
+
+        // 连上后什么都不发送的客户端
+        let _idle = TcpStream::connect(addr).unwrap();
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .set_read_timeout(Some(CONNECTION_TIMEOUT * 5))
+            .unwrap();
+        client.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+    }
+
+    #[test]
+    fn test_render_state_empty() {
+        let text = render_state(&MetricsState::new());
+        assert!(text.contains("edge_copilot_helper_edge_exit_events_total 0"));
+        assert!(!text.contains("monitor_backend_info{"));
+        assert!(!text.contains("last_success_timestamp_seconds "));
+    }
+}
@@ -1,41 +1,208 @@
 #![cfg(not(target_os = "macos"))]
 
 use anyhow::Result;
-use std::{thread, time::Duration};
+use std::time::{Duration, Instant};
 use sysinfo::System;
 
-use crate::common::apply_fix;
+use crate::common::{FixOptions, apply_fix};
 use crate::constants::edge::PROCESS_NAMES;
+use crate::metrics;
+use crate::shutdown;
+#[cfg(target_os = "linux")]
+use crate::systemd;
+use crate::watch::{ExitDebouncer, ExitEvent, WatchOptions};
 
 /// 运行轮询监控循环
 ///
-/// 在 Windows 和 Linux 平台上使用，每 2 秒检查一次 Edge 进程状态。
-/// 当检测到 Edge 退出时，自动应用配置修复。
-pub fn run_polling_loop() -> Result<()> {
+/// 在 Windows 和 Linux 平台上使用。Edge 运行时以 `watch.active_interval` 快速轮询，
+/// 以便在 Edge 快速重启前发现退出；Edge 关闭后从 `watch.idle_interval` 开始逐渐放慢，
+/// 关闭很久或用户会话锁定 / 空闲时使用 `watch.max_idle_interval`。
+/// Edge 退出后保持未运行 `watch.quiet_period` 才应用配置修复，
+/// 期间 Edge 重新启动（更新、“重启以应用”）则取消本次修复。
+/// 在 Linux 上由 systemd 启动时，会发送 `READY=1`、周期性 `WATCHDOG=1`
+/// 以及描述最近一次操作的 `STATUS=`。
+/// 收到退出请求（信号或 `--replace`）后，在当前修复完成后返回。
+pub fn run_polling_loop(options: &FixOptions, watch: &WatchOptions) -> Result<()> {
     log::info!("🐧/🪟 Polling Mode: Starting Loop...");
     let process_list = PROCESS_NAMES.join(", ");
     log::info!("   Monitoring process: {process_list}");
+    metrics::set_backend("polling");
 
     let mut sys = System::new();
-    let mut was_running = false;
+    let mut debouncer = ExitDebouncer::new(watch.quiet_period);
+    let mut closed_since = Instant::now();
+    let mut interval = Duration::ZERO;
+    let mut session = SessionIdle::default();
+    #[cfg(target_os = "linux")]
+    let watchdog_interval = systemd::watchdog_interval();
 
-    loop {
+    #[cfg(target_os = "linux")]
+    {
+        systemd::ready();
+        systemd::status("Monitoring Edge, no fix applied yet");
+    }
+
+    while !shutdown::requested() {
         sys.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
 
-        // Check if any Edge process exists
-        let is_running = sys.processes().values().any(|process| {
-            let pname = process.name().to_string_lossy();
-            PROCESS_NAMES.iter().any(|&n| n == pname)
-        });
+        let is_running = edge_running(&sys);
+        let now = Instant::now();
+        if is_running {
+            closed_since = now;
+        }
+
+        match debouncer.observe(is_running, now) {
+            ExitEvent::Pending => log::info!(
+                "🛑 Edge exited. Waiting {}s in case it restarts...",
+                watch.quiet_period.as_secs()
+            ),
+            ExitEvent::Cancelled => {
+                log::info!("🔄 Edge started again within the quiet period; skipping the fix")
+            }
+            ExitEvent::None => {}
+            ExitEvent::Confirmed => {
+                log::info!("🛑 Edge stayed closed. Applying fix...");
+                metrics::record_exit_event();
+                let result = {
+                    let _patching = shutdown::patching();
+                    apply_fix(options)
+                };
+                match &result {
+                    Ok(report) => report.log_summary(),
+                    Err(e) => log::error!("❌ Failed to apply fix: {e}"),
+                }
+
+                #[cfg(target_os = "linux")]
+                {
+                    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+                    match &result {
+                        Ok(report) if report.is_success() => {
+                            systemd::status(&format!("Edge exited at {now}, fix applied"))
+                        }
+                        Ok(report) => systemd::status(&format!(
+                            "Edge exited at {now}, fix partially failed: {}",
+                            report.summary()
+                        )),
+                        Err(e) => {
+                            systemd::status(&format!("Edge exited at {now}, fix failed: {e}"))
+                        }
+                    }
+                }
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        systemd::watchdog();
+
+        if is_running {
+            // Edge 在运行，用户显然不空闲
+            session.forget();
+        }
+        let active = is_running || debouncer.is_pending();
+        let closed_for = closed_since.elapsed();
+        let mut next = watch.poll_interval(active, closed_for, false);
+        // 只有会话空闲会让间隔变长时才查询（Linux 上需要启动 loginctl）
+        let idle_interval = watch.poll_interval(active, closed_for, true);
+        if next < idle_interval && session.is_idle(watch.max_idle_interval) {
+            next = idle_interval;
+        }
+        // 轮询间隔再长也要按时喂 systemd 看门狗
+        #[cfg(target_os = "linux")]
+        if let Some(watchdog_interval) = watchdog_interval {
+            next = next.min(watchdog_interval);
+        }
+        if next != interval {
+            log::debug!("Polling interval is now {} ms", next.as_millis());
+            interval = next;
+        }
+        shutdown::sleep(interval);
+    }
+
+    #[cfg(target_os = "linux")]
+    systemd::stopping();
+    Ok(())
+}
+
+/// Edge 当前是否正在运行
+pub fn is_edge_running() -> bool {
+    let mut sys = System::new();
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+    edge_running(&sys)
+}
+
+/// 检查进程列表中是否有 Edge 进程
+fn edge_running(sys: &System) -> bool {
+    sys.processes().values().any(|process| {
+        let pname = process.name().to_string_lossy();
+        PROCESS_NAMES.iter().any(|&n| n == pname)
+    })
+}
 
-        if was_running && !is_running {
-            log::info!("🛑 Edge exited. Applying fix...");
-            if let Err(e) = apply_fix() {
-                log::error!("❌ Failed to apply fix: {e}");
+/// 会话空闲状态的缓存，最多每 `max_age` 查询一次
+#[derive(Default)]
+struct SessionIdle {
+    checked: Option<(Instant, bool)>,
+}
+
+impl SessionIdle {
+    fn is_idle(&mut self, max_age: Duration) -> bool {
+        match self.checked {
+            Some((at, idle)) if at.elapsed() < max_age => idle,
+            _ => {
+                let idle = session_idle();
+                self.checked = Some((Instant::now(), idle));
+                idle
             }
         }
+    }
 
-        was_running = is_running;
-        thread::sleep(Duration::from_secs(2));
+    /// 丢弃缓存，下次重新查询
+    fn forget(&mut self) {
+        self.checked = None;
+    }
+}
+
+/// 会话空闲多久后放慢轮询（Windows）
+#[cfg(target_os = "windows")]
+const SESSION_IDLE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// 当前用户会话是否已锁定或空闲，无法判断时视为否
+///
+/// 通过 logind 的 `LockedHint` / `IdleHint` 判断（需要桌面环境上报）。
+#[cfg(target_os = "linux")]
+fn session_idle() -> bool {
+    std::process::Command::new("loginctl")
+        .args([
+            "show-session",
+            "auto",
+            "--property=LockedHint",
+            "--property=IdleHint",
+            "--value",
+        ])
+        .output()
+        .is_ok_and(|output| {
+            output.status.success()
+                && String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .any(|line| line.trim() == "yes")
+        })
+}
+
+/// 当前用户会话是否空闲（超过 [`SESSION_IDLE_AFTER`] 没有键盘鼠标输入，锁屏时同样如此）
+#[cfg(target_os = "windows")]
+fn session_idle() -> bool {
+    use winapi::um::sysinfoapi::GetTickCount;
+    use winapi::um::winuser::{GetLastInputInfo, LASTINPUTINFO};
+
+    let mut info = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    unsafe {
+        if GetLastInputInfo(&mut info) == 0 {
+            return false;
+        }
+        let idle_ms = GetTickCount().wrapping_sub(info.dwTime);
+        Duration::from_millis(idle_ms.into()) >= SESSION_IDLE_AFTER
     }
 }
@@ -0,0 +1,326 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::safety::{self, Verdict};
+
+/// 每个配置文件保留的备份数
+const BACKUPS_PER_FILE: usize = 3;
+
+/// 修复损坏文件的结果
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Repair {
+    /// 已用可解析的副本替换损坏的文件
+    Restored { from: PathBuf, quarantined: PathBuf },
+    /// 没有可用的副本，损坏的文件已移走，Edge 下次启动时会重新生成
+    Quarantined(PathBuf),
+}
+
+/// 写入前备份配置文件的原内容，每个文件只保留最近 [`BACKUPS_PER_FILE`] 份
+///
+/// 备份位于 `backup_dir` 下以配置文件绝对路径哈希命名的子目录中，文件名为时间戳。
+/// 配置文件可能包含隐私数据，Unix 上备份目录权限为 0700、备份文件为 0600。
+pub(crate) fn save_backup(backup_dir: &Path, target: &Path, content: &str) -> Result<()> {
+    let dir = backups_of(backup_dir, target);
+    private_dir_builder()
+        .create(&dir)
+        .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+
+    let name = chrono::Local::now().format("%Y%m%dT%H%M%S%.6f.json");
+    let backup = dir.join(name.to_string());
+    private_file_options()
+        .open(&backup)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .with_context(|| format!("Failed to write backup {}", backup.display()))?;
+
+    for old in list_backups(&dir).into_iter().skip(BACKUPS_PER_FILE) {
+        let _ = fs::remove_file(old);
+    }
+    Ok(())
+}
+
+/// 修复无法解析的配置文件
+///
+/// 在 helper 保存的备份和 Edge 自己留下的 `<文件名>.bak` 中按修改时间从新到旧尝试，
+/// 使用第一个能解析为 JSON 对象的副本（helper 的备份可能是几周前修复时留下的，
+/// 不能无条件优先）；损坏的内容以 `<文件名>.corrupt-<时间戳>` 保留在原目录，不会覆盖之前隔离的文件。
+/// 没有可用副本时只移走损坏的文件，让 Edge 下次启动时重新生成。
+pub(crate) fn repair(backup_dir: &Path, target: &Path, path: &Path) -> Result<Repair> {
+    let mut candidates = list_backups(&backups_of(backup_dir, target));
+    candidates.push(sibling(path, ".bak"));
+    // 读不到修改时间的副本排在最后；时间相同时保持原顺序（helper 的备份在前）
+    candidates.sort_by_cached_key(|candidate| {
+        std::cmp::Reverse(fs::metadata(candidate).and_then(|m| m.modified()).ok())
+    });
+
+    for candidate in candidates {
+        // `.bak` 与配置文件位于同一目录，可能同样被换成指向别处或属于其他用户的文件
+        let real = match safety::check(&candidate) {
+            Ok(Verdict::Safe(real)) => real,
+            Ok(Verdict::Refused(reason)) => {
+                log::warn!("🔒 Skipping copy {}: {reason}", candidate.display());
+                continue;
+            }
+            Err(_) => continue,
+        };
+        let Some(content) = safety::read_to_string(&real).ok() else {
+            continue;
+        };
+        if !serde_json::from_str::<Value>(&content).is_ok_and(|json| json.is_object()) {
+            log::info!("Skipping unusable copy {}", candidate.display());
+            continue;
+        }
+
+        // 原地写回而不是替换文件，调用方在该文件上持有的锁保持有效
+        let quarantined = preserve(path)?;
+        safety::write(path, content)
+            .with_context(|| format!("Failed to restore {}", path.display()))?;
+        return Ok(Repair::Restored {
+            from: candidate,
+            quarantined,
+        });
+    }
+
+    Ok(Repair::Quarantined(quarantine(path)?))
+}
+
+/// 某个配置文件的备份目录
+fn backups_of(backup_dir: &Path, target: &Path) -> PathBuf {
+    let digest = Sha256::digest(target.as_os_str().as_encoded_bytes());
+    let name: String = digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+    backup_dir.join(name)
+}
+
+/// 目录中的备份，最新的在前
+fn list_backups(dir: &Path) -> Vec<PathBuf> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .collect()
+        })
+        .unwrap_or_default();
+    backups.sort_unstable_by(|a, b| b.cmp(a));
+    backups
+}
+
+#[cfg(unix)]
+fn private_dir_builder() -> fs::DirBuilder {
+    use std::os::unix::fs::DirBuilderExt;
+
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true).mode(0o700);
+    builder
+}
+
+#[cfg(not(unix))]
+fn private_dir_builder() -> fs::DirBuilder {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    builder
+}
+
+#[cfg(unix)]
+fn private_file_options() -> fs::OpenOptions {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true).mode(0o600);
+    options
+}
+
+#[cfg(not(unix))]
+fn private_file_options() -> fs::OpenOptions {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    options
+}
+
+/// 同目录下在文件名后追加后缀的路径
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// 把损坏文件的内容复制到新的隔离文件，原文件保持不动；返回隔离文件路径
+fn preserve(path: &Path) -> Result<PathBuf> {
+    let mut content = Vec::new();
+    safety::open_options()
+        .read(true)
+        .open(path)
+        .and_then(|mut file| std::io::Read::read_to_end(&mut file, &mut content))
+        .with_context(|| format!("Failed to read corrupt {}", path.display()))?;
+    claim_quarantine_name(path, |to| {
+        private_file_options()
+            .open(to)
+            .and_then(|mut file| file.write_all(&content))
+    })
+    .with_context(|| format!("Failed to copy corrupt {}", path.display()))
+}
+
+/// 把损坏的文件移到新的隔离文件；返回隔离文件路径
+fn quarantine(path: &Path) -> Result<PathBuf> {
+    // 硬链接不会覆盖已存在的文件，rename 会
+    claim_quarantine_name(path, |to| {
+        fs::hard_link(path, to).and_then(|_| fs::remove_file(path))
+    })
+    .with_context(|| format!("Failed to move corrupt {}", path.display()))
+}
+
+/// 以 `<文件名>.corrupt-<时间戳>` 为名执行 `create`，同一秒内已有隔离文件时依次追加 `-1`、`-2`…
+///
+/// `create` 在目标已存在时必须返回 [`io::ErrorKind::AlreadyExists`] 而不是覆盖它。
+fn claim_quarantine_name(
+    path: &Path,
+    mut create: impl FnMut(&Path) -> io::Result<()>,
+) -> io::Result<PathBuf> {
+    let stem = format!(".corrupt-{}", chrono::Local::now().format("%Y%m%d%H%M%S"));
+    for attempt in 0..100 {
+        let to = if attempt == 0 {
+            sibling(path, &stem)
+        } else {
+            sibling(path, &format!("{stem}-{attempt}"))
+        };
+        match create(&to) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            result => return result.map(|_| to),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "too many quarantined copies in the same second",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repair_prefers_newest_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_dir = dir.path().join("backups");
+        let prefs = dir.path().join("Preferences");
+
+        // 只有 Edge 的 .bak 可用
+        fs::write(&prefs, r#"{"browser":"#).unwrap();
+        fs::write(sibling(&prefs, ".bak"), r#"{"from":"edge"}"#).unwrap();
+        let Repair::Restored { from, quarantined } = repair(&backup_dir, &prefs, &prefs).unwrap()
+        else {
+            panic!("expected a restore");
+        };
+        assert_eq!(from, sibling(&prefs, ".bak"));
+        assert_eq!(fs::read_to_string(&quarantined).unwrap(), r#"{"browser":"#);
+        assert_eq!(fs::read_to_string(&prefs).unwrap(), r#"{"from":"edge"}"#);
+
+        // helper 的备份比 .bak 新时使用最新的备份，旧备份被清理
+        set_modified(&sibling(&prefs, ".bak"), 3600);
+        for i in 0..5 {
+            save_backup(&backup_dir, &prefs, &format!(r#"{{"backup":{i}}}"#)).unwrap();
+        }
+        assert_eq!(list_backups(&backups_of(&backup_dir, &prefs)).len(), 3);
+        fs::write(&prefs, "").unwrap();
+        repair(&backup_dir, &prefs, &prefs).unwrap();
+        assert_eq!(fs::read_to_string(&prefs).unwrap(), r#"{"backup":4}"#);
+
+        // Edge 的 .bak 比几周前的 helper 备份新时使用 .bak
+        for backup in list_backups(&backups_of(&backup_dir, &prefs)) {
+            set_modified(&backup, 21 * 24 * 3600);
+        }
+        fs::write(sibling(&prefs, ".bak"), r#"{"from":"edge"}"#).unwrap();
+        fs::write(&prefs, "").unwrap();
+        repair(&backup_dir, &prefs, &prefs).unwrap();
+        assert_eq!(fs::read_to_string(&prefs).unwrap(), r#"{"from":"edge"}"#);
+    }
+
+    /// 把文件的修改时间设为 `secs_ago` 秒之前
+    fn set_modified(path: &Path, secs_ago: u64) {
+        let time = std::time::SystemTime::now() - std::time::Duration::from_secs(secs_ago);
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[cfg(unix)]
+    #[test]
+    fn test_repair_skips_symlinked_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let user_data = dir.path().join("microsoft-edge");
+        let profile = user_data.join("Default");
+        fs::create_dir_all(&profile).unwrap();
+        let prefs = profile.join("Preferences");
+        fs::write(&prefs, r#"{"browser":"#).unwrap();
+        fs::write(dir.path().join("secret.json"), r#"{"secret":true}"#).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("secret.json"), sibling(&prefs, ".bak"))
+            .unwrap();
+
+        let repaired = repair(&dir.path().join("backups"), &prefs, &prefs).unwrap();
+        assert!(matches!(repaired, Repair::Quarantined(_)));
+        assert!(!prefs.exists());
+    }
+
+    #[test]
+    fn test_backups_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let prefs = dir.path().join("Preferences");
+        save_backup(&dir.path().join("backups"), &prefs, "{}").unwrap();
+
+        let backups = backups_of(&dir.path().join("backups"), &prefs);
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&backups), 0o700);
+        assert_eq!(mode(&list_backups(&backups)[0]), 0o600);
+    }
+
+    #[test]
+    fn test_quarantine_never_overwrites_earlier_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_dir = dir.path().join("backups");
+        let prefs = dir.path().join("Preferences");
+
+        let mut quarantined = Vec::new();
+        for i in 0..3 {
+            fs::write(&prefs, format!("corrupt {i}")).unwrap();
+            let Repair::Quarantined(path) = repair(&backup_dir, &prefs, &prefs).unwrap() else {
+                panic!("expected a quarantine");
+            };
+            quarantined.push(path);
+        }
+        quarantined.sort();
+        quarantined.dedup();
+        assert_eq!(quarantined.len(), 3);
+        let mut contents: Vec<String> = quarantined
+            .iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect();
+        contents.sort();
+        assert_eq!(contents, ["corrupt 0", "corrupt 1", "corrupt 2"]);
+    }
+
+    #[test]
+    fn test_repair_quarantines_when_no_copy_is_usable() {
+        let dir = tempfile::tempdir().unwrap();
+        let local_state = dir.path().join("Local State");
+        fs::write(&local_state, "\0\0\0").unwrap();
+        fs::write(sibling(&local_state, ".bak"), "not json").unwrap();
+
+        let Repair::Quarantined(quarantined) =
+            repair(&dir.path().join("backups"), &local_state, &local_state).unwrap()
+        else {
+            panic!("expected quarantine");
+        };
+        assert!(!local_state.exists());
+        assert_eq!(fs::read_to_string(quarantined).unwrap(), "\0\0\0");
+    }
+}
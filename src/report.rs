@@ -0,0 +1,205 @@
+use serde_json::{Value, json};
+use std::path::PathBuf;
+
+use crate::error::Error;
+
+/// 单个配置文件的处理结果
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FileOutcome {
+    /// 已修改并写回
+    Patched,
+    /// 已是目标值，无需修改
+    Unchanged,
+    /// 未处理（原因）
+    Skipped(String),
+    /// 安全检查未通过而拒绝修改（原因），如指向用户数据目录之外的符号链接、属于其他用户的文件
+    Unsafe(String),
+    /// 处理失败
+    Failed(Error),
+}
+
+impl From<bool> for FileOutcome {
+    /// 文件是否已修改并写回
+    fn from(modified: bool) -> Self {
+        if modified {
+            Self::Patched
+        } else {
+            Self::Unchanged
+        }
+    }
+}
+
+/// 单个配置文件的处理记录
+#[derive(Debug)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub file_type: String,
+    pub outcome: FileOutcome,
+}
+
+/// 一次 [`apply_fix`](crate::apply_fix) 的汇总结果
+///
+/// 单个文件失败不会中断其余文件的处理，失败记录在报告中。
+#[derive(Debug, Default)]
+pub struct ApplyReport {
+    pub files: Vec<FileReport>,
+}
+
+impl ApplyReport {
+    pub(crate) fn push(&mut self, path: PathBuf, file_type: &str, outcome: FileOutcome) {
+        self.files.push(FileReport {
+            path,
+            file_type: file_type.to_string(),
+            outcome,
+        });
+    }
+
+    /// 已修改的文件数
+    pub fn patched(&self) -> usize {
+        self.count(|outcome| matches!(outcome, FileOutcome::Patched))
+    }
+
+    /// 无需修改的文件数
+    pub fn unchanged(&self) -> usize {
+        self.count(|outcome| matches!(outcome, FileOutcome::Unchanged))
+    }
+
+    /// 跳过的文件数（包括安全检查未通过的文件）
+    pub fn skipped(&self) -> usize {
+        self.count(|outcome| matches!(outcome, FileOutcome::Skipped(_) | FileOutcome::Unsafe(_)))
+    }
+
+    /// 因安全检查未通过而拒绝修改的文件数
+    pub fn refused(&self) -> usize {
+        self.count(|outcome| matches!(outcome, FileOutcome::Unsafe(_)))
+    }
+
+    /// 处理失败的文件
+    pub fn failures(&self) -> impl Iterator<Item = (&FileReport, &Error)> {
+        self.files.iter().filter_map(|file| match &file.outcome {
+            FileOutcome::Failed(failure) => Some((file, failure)),
+            _ => None,
+        })
+    }
+
+    /// 是否所有文件都处理成功
+    ///
+    /// 找到的文件全部被安全检查拒绝时也视为失败：此时什么都没有修复。
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+            && (self.refused() == 0 || self.refused() < self.files.len())
+    }
+
+    /// 一行摘要，如 `3 patched, 5 unchanged, 0 skipped, 1 failed`
+    pub fn summary(&self) -> String {
+        format!(
+            "{} patched, {} unchanged, {} skipped, {} failed",
+            self.patched(),
+            self.unchanged(),
+            self.skipped(),
+            self.failures().count()
+        )
+    }
+
+    /// 记录摘要与每个失败的文件
+    pub fn log_summary(&self) {
+        for (file, error) in self.failures() {
+            log::error!(
+                "❌ {} at {}: [{}] {}",
+                file.file_type,
+                file.path.display(),
+                error.code(),
+                error.message()
+            );
+        }
+        if self.is_success() {
+            log::info!("📋 Summary: {}", self.summary());
+        } else {
+            log::warn!("📋 Summary: {}", self.summary());
+        }
+    }
+
+    /// JSON 形式的报告（`apply --json` 输出）
+    pub fn to_json(&self) -> Value {
+        let files: Vec<Value> = self
+            .files
+            .iter()
+            .map(|file| {
+                let mut entry = json!({
+                    "path": file.path,
+                    "file_type": file.file_type,
+                });
+                let (outcome, detail) = match &file.outcome {
+                    FileOutcome::Patched => ("patched", None),
+                    FileOutcome::Unchanged => ("unchanged", None),
+                    FileOutcome::Skipped(reason) => ("skipped", Some(json!({ "reason": reason }))),
+                    FileOutcome::Unsafe(reason) => (
+                        "skipped",
+                        Some(json!({ "reason": reason, "security": true })),
+                    ),
+                    FileOutcome::Failed(error) => (
+                        "failed",
+                        Some(json!({
+                            "error": { "code": error.code(), "message": error.message() }
+                        })),
+                    ),
+                };
+                entry["outcome"] = json!(outcome);
+                if let Some(Value::Object(detail)) = detail {
+                    entry.as_object_mut().unwrap().extend(detail);
+                }
+                entry
+            })
+            .collect();
+
+        json!({
+            "success": self.is_success(),
+            "summary": {
+                "patched": self.patched(),
+                "unchanged": self.unchanged(),
+                "skipped": self.skipped(),
+                "failed": self.failures().count(),
+            },
+            "files": files,
+        })
+    }
+
+    fn count(&self, predicate: impl Fn(&FileOutcome) -> bool) -> usize {
+        self.files
+            .iter()
+            .filter(|file| predicate(&file.outcome))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_counts_outcomes() {
+        let mut report = ApplyReport::default();
+        report.push("a".into(), "Local State", FileOutcome::Patched);
+        report.push("b".into(), "Preferences", FileOutcome::Unchanged);
+        report.push(
+            "c".into(),
+            "Preferences",
+            FileOutcome::Failed(Error::Parse {
+                path: "c".into(),
+                source: serde_json::from_str::<Value>("{").unwrap_err(),
+            }),
+        );
+
+        assert!(!report.is_success());
+        assert_eq!(
+            report.summary(),
+            "1 patched, 1 unchanged, 0 skipped, 1 failed"
+        );
+
+        let json = report.to_json();
+        assert_eq!(json["summary"]["failed"], json!(1));
+        assert_eq!(json["files"][2]["outcome"], json!("failed"));
+        assert_eq!(json["files"][2]["error"]["code"], json!("E_PARSE"));
+    }
+}
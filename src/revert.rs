@@ -0,0 +1,145 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::common::JsonStyle;
+use crate::keypath;
+use crate::lock::{self, is_instance_running};
+use crate::safety::{self, Verdict};
+use crate::shutdown;
+use crate::state::{FileState, OriginalValue, State};
+
+/// 默认等待 Edge 关闭的最长时间
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// [`revert`] 的选项
+#[derive(Debug, Clone)]
+pub struct RevertOptions {
+    /// Edge 运行时等待其关闭的最长时间，超时后放弃撤销、不修改任何文件
+    pub wait_timeout: Duration,
+}
+
+impl Default for RevertOptions {
+    fn default() -> Self {
+        Self {
+            wait_timeout: DEFAULT_WAIT_TIMEOUT,
+        }
+    }
+}
+
+/// 撤销 Copilot 区域修复
+///
+/// 把状态数据库中记录的每个键恢复为首次修改前的值（原本不存在的键会被删除）。
+/// Edge 退出时会写回自己的配置，因此先等待 Edge 关闭（最长 `options.wait_timeout`）再修改文件。
+/// 恢复失败的文件保留在状态数据库中，可以再次执行 revert。
+pub fn revert(options: &RevertOptions) -> Result<()> {
+    let mut state = State::load(&State::default_path())?;
+    if state.files.values().all(|file| file.original.is_empty()) {
+        log::info!("ℹ️ Nothing to revert: no recorded changes.");
+        return Ok(());
+    }
+
+    if is_instance_running() {
+        log::warn!(
+            "⚠️ The helper is still running and will re-apply the fix when Edge exits; use `uninstall --revert` to remove it as well."
+        );
+    }
+
+    wait_for_edge_to_close(options.wait_timeout)?;
+
+    let mut failed = 0;
+    for (path, file_state) in state
+        .files
+        .iter_mut()
+        .filter(|(_, file)| !file.original.is_empty())
+    {
+        match revert_file(path, file_state) {
+            Ok(reverted) => {
+                if reverted {
+                    log::info!("↩️ Reverted {}", path.display());
+                } else {
+                    log::info!("Nothing to revert in {}", path.display());
+                }
+                // 保留修改历史，只清除原始值
+                file_state.original.clear();
+                file_state.applied = false;
+            }
+            Err(e) => {
+                log::error!("❌ Failed to revert {}: {e:#}", path.display());
+                failed += 1;
+            }
+        }
+    }
+    state.save()?;
+
+    if failed > 0 {
+        anyhow::bail!(
+            "Failed to revert {failed} file(s); run revert again after fixing the errors"
+        );
+    }
+
+    log::info!("Revert complete.");
+    Ok(())
+}
+
+/// 恢复单个文件中记录的键，文件已不存在时视为无需恢复
+fn revert_file(path: &Path, file_state: &FileState) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let path = match safety::check(path)? {
+        Verdict::Safe(real) => real,
+        Verdict::Refused(reason) => {
+            anyhow::bail!("Refusing to modify {}: {reason}", path.display())
+        }
+    };
+    let path = path.as_path();
+    let _lock = lock::lock_config_file(path)?;
+
+    let content = safety::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut json: Value = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse JSON at {}", path.display()))?;
+
+    let mut modified = false;
+    for (key, original) in &file_state.original {
+        modified |= match original {
+            OriginalValue::Present(value) => keypath::set(&mut json, key, value.clone()),
+            OriginalValue::Missing => keypath::remove(&mut json, key),
+        };
+    }
+
+    if modified {
+        let new_content = JsonStyle::detect(&content).serialize(&json)?;
+        safety::write(path, new_content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(modified)
+}
+
+/// Edge 运行时等待其关闭，超过 `timeout` 或收到退出请求时返回错误
+fn wait_for_edge_to_close(timeout: Duration) -> Result<()> {
+    if !crate::is_edge_running() {
+        return Ok(());
+    }
+
+    log::info!(
+        "⏳ Waiting up to {}s for Edge to close...",
+        timeout.as_secs()
+    );
+    let deadline = Instant::now() + timeout;
+    while crate::is_edge_running() {
+        if shutdown::requested() {
+            anyhow::bail!("Interrupted while waiting for Edge to close; nothing was reverted");
+        }
+        if Instant::now() >= deadline {
+            anyhow::bail!(
+                "Edge is still running after {}s; close Edge and run revert again (see --wait-timeout)",
+                timeout.as_secs()
+            );
+        }
+        shutdown::sleep(Duration::from_secs(2));
+    }
+    Ok(())
+}
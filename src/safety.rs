@@ -0,0 +1,171 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::version;
+
+/// 写入前安全检查的结论
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Verdict {
+    /// 可以修改；后续读写使用解析符号链接后的真实路径
+    Safe(PathBuf),
+    /// 拒绝修改（原因）
+    Refused(String),
+}
+
+/// 检查配置文件能否安全地修改
+///
+/// 在共享或配置有误的主目录中，`Preferences` 可能是指向别处的符号链接，或属于其他用户。
+/// 以下情况拒绝修改：
+/// - 解析符号链接后文件不在 Edge 用户数据目录内（用户数据目录本身是符号链接不受影响）
+/// - 不是普通文件
+/// - Unix 上文件不属于当前用户；只有以 root 身份运行时（通过 `--home`
+///   修复其他用户的主目录）才改为要求文件属于 Edge 用户数据目录的所有者
+pub(crate) fn check(path: &Path) -> Result<Verdict, Error> {
+    let real = fs::canonicalize(path).map_err(|e| Error::read(path, e))?;
+
+    let root = match version::user_data_dir_of(path) {
+        Some(dir) => Some(fs::canonicalize(dir).map_err(|e| Error::read(dir, e))?),
+        None => None,
+    };
+    if let Some(root) = &root
+        && !real.starts_with(root)
+    {
+        return Ok(Verdict::Refused(format!(
+            "resolves to {}, outside the Edge user data directory {}",
+            real.display(),
+            root.display()
+        )));
+    }
+
+    let metadata = fs::metadata(&real).map_err(|e| Error::read(&real, e))?;
+    if !metadata.is_file() {
+        return Ok(Verdict::Refused("not a regular file".to_string()));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let root_uid = match &root {
+            Some(root) => Some(fs::metadata(root).map_err(|e| Error::read(root, e))?.uid()),
+            None => None,
+        };
+        if let Some(reason) = owner_mismatch(metadata.uid(), current_uid(), root_uid) {
+            return Ok(Verdict::Refused(reason));
+        }
+    }
+
+    Ok(Verdict::Safe(real))
+}
+
+/// 文件所有者不符合要求时返回拒绝原因
+///
+/// 普通用户只能修改自己的文件，即使其他用户的用户数据目录对其可写；
+/// root 修改的文件必须属于用户数据目录（没有时为 root 自己）的所有者。
+#[cfg(unix)]
+fn owner_mismatch(file_uid: u32, euid: u32, root_uid: Option<u32>) -> Option<String> {
+    let (uid, owner) = match root_uid {
+        Some(root_uid) if euid == 0 => (root_uid, "the owner of the Edge user data directory"),
+        _ => (euid, "the current user"),
+    };
+    (file_uid != uid).then(|| format!("owned by UID {file_uid}, not {owner} (UID {uid})"))
+}
+
+/// 读取文件；Unix 上不跟随最后一级的符号链接
+pub(crate) fn read_to_string(path: &Path) -> io::Result<String> {
+    io::read_to_string(open_options().read(true).open(path)?)
+}
+
+/// 写入文件（不存在时创建）；Unix 上不跟随最后一级的符号链接，
+/// 防止文件在检查之后被换成指向别处的链接
+pub(crate) fn write(path: &Path, content: impl AsRef<[u8]>) -> io::Result<()> {
+    open_options()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?
+        .write_all(content.as_ref())
+}
+
+/// 打开文件的选项；Unix 上不跟随最后一级的符号链接
+#[cfg(unix)]
+pub(crate) fn open_options() -> fs::OpenOptions {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut options = fs::OpenOptions::new();
+    options.custom_flags(libc::O_NOFOLLOW);
+    options
+}
+
+/// 打开文件的选项；Unix 上不跟随最后一级的符号链接
+#[cfg(not(unix))]
+pub(crate) fn open_options() -> fs::OpenOptions {
+    fs::OpenOptions::new()
+}
+
+/// 当前进程的有效 UID
+#[cfg(unix)]
+pub(crate) fn current_uid() -> u32 {
+    // SAFETY: geteuid 没有前置条件，也不会失败
+    unsafe { libc::geteuid() }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_check_refuses_symlinks_escaping_user_data_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let user_data = dir.path().join("microsoft-edge");
+        let profile = user_data.join("Default");
+        fs::create_dir_all(&profile).unwrap();
+        fs::write(dir.path().join("outside.json"), "{}").unwrap();
+        fs::write(user_data.join("shared.json"), "{}").unwrap();
+
+        let prefs = profile.join("Preferences");
+        symlink(dir.path().join("outside.json"), &prefs).unwrap();
+        assert!(
+            matches!(check(&prefs).unwrap(), Verdict::Refused(reason) if reason.contains("outside"))
+        );
+
+        // 指向用户数据目录内部的链接可以修改，读写使用真实路径
+        fs::remove_file(&prefs).unwrap();
+        symlink(user_data.join("shared.json"), &prefs).unwrap();
+        assert_eq!(
+            check(&prefs).unwrap(),
+            Verdict::Safe(fs::canonicalize(user_data.join("shared.json")).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_owner_mismatch() {
+        // 普通用户不修改其他用户的文件，即使对方的用户数据目录可写
+        assert!(owner_mismatch(1001, 1000, Some(1001)).is_some());
+        assert!(owner_mismatch(1001, 1000, None).is_some());
+        assert!(owner_mismatch(1000, 1000, Some(1001)).is_none());
+        // root 修改其他用户的主目录时要求文件属于该目录的所有者
+        assert!(owner_mismatch(1001, 0, Some(1001)).is_none());
+        assert!(owner_mismatch(1002, 0, Some(1001)).is_some());
+        assert!(owner_mismatch(0, 0, None).is_none());
+    }
+
+    #[test]
+    fn test_read_and_write_do_not_follow_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target.json");
+        let link = dir.path().join("Preferences");
+        fs::write(&target, "{}").unwrap();
+        symlink(&target, &link).unwrap();
+
+        assert!(read_to_string(&link).is_err());
+        assert!(write(&link, "[]").is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "{}");
+
+        write(&target, "[]").unwrap();
+        assert_eq!(read_to_string(&target).unwrap(), "[]");
+    }
+}
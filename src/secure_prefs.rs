@@ -0,0 +1,117 @@
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::common::{PatchContext, process_json_file};
+use crate::keypath;
+use crate::report::FileOutcome;
+use crate::state::State;
+
+/// Secure Preferences 文件名（与 Preferences 位于同一 Profile 目录）
+pub const SECURE_PREFERENCES_FILE: &str = "Secure Preferences";
+
+/// 可选规则集：可能受 MAC 保护的键及其目标值
+///
+/// 与 `Preferences` / `Local State` 的默认修复相互独立，只有启用
+/// `FixOptions::secure_preferences` 时才会写入 Secure Preferences，并且只修改已存在的条目。
+pub const SECURE_RULES: &[(&str, bool)] = &[("browser.chat_ip_eligibility_status", true)];
+
+/// 检查 Profile 中被修复的键是否受 `protection.macs` 保护，并按需修复 Secure Preferences
+///
+/// - `ctx`：Profile 所属 Edge 的版本与修复选项
+/// - `prefs_modified`：本次是否修改了该 Profile 的 `Preferences`，只有修改过才报告被重置的风险
+/// - `patch`：是否按 [`SECURE_RULES`] 修改 Secure Preferences 中对应的条目
+///
+/// 返回 Secure Preferences 的处理结果；未启用 `patch` 或文件不存在时返回 `None`。
+pub(crate) fn process_profile(
+    profile_dir: &Path,
+    state: &mut State,
+    ctx: PatchContext,
+    prefs_modified: bool,
+    patch: bool,
+) -> Option<FileOutcome> {
+    let prefs_path = profile_dir.join("Preferences");
+    let secure_path = profile_dir.join(SECURE_PREFERENCES_FILE);
+    let prefs = read_json(&prefs_path);
+    let secure = read_json(&secure_path);
+
+    for &(key, target) in SECURE_RULES {
+        if prefs_modified {
+            for (path, json) in [(&prefs_path, &prefs), (&secure_path, &secure)] {
+                if json
+                    .as_ref()
+                    .is_some_and(|json| is_mac_protected(json, key))
+                {
+                    log::warn!(
+                        "⚠️ {key} is MAC-protected in {}; Edge may treat the change as tampering and reset it",
+                        path.display()
+                    );
+                }
+            }
+        }
+
+        let stored = secure.as_ref().and_then(|json| keypath::get(json, key));
+        if !patch && stored.is_some_and(|value| value.as_bool() != Some(target)) {
+            log::warn!(
+                "⚠️ {key} is overridden in {}; pass --secure-preferences to patch it as well",
+                secure_path.display()
+            );
+        }
+    }
+
+    if !patch || secure.is_none() {
+        return None;
+    }
+
+    let keys: Vec<&str> = SECURE_RULES.iter().map(|&(key, _)| key).collect();
+    let outcome = process_json_file(
+        &secure_path,
+        SECURE_PREFERENCES_FILE,
+        &keys,
+        state,
+        ctx,
+        |json| {
+            SECURE_RULES.iter().fold(false, |modified, &(key, target)| {
+                keypath::replace_existing(json, key, Value::Bool(target)) || modified
+            })
+        },
+    );
+    Some(outcome)
+}
+
+/// 读取 JSON 文件，不存在或无法解析时返回 `None`
+fn read_json(path: &Path) -> Option<Value> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// 键是否在 `protection.macs` 中有对应的 MAC
+fn is_mac_protected(json: &Value, key_path: &str) -> bool {
+    keypath::get(json, &format!("protection.macs.{key_path}")).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_is_mac_protected() {
+        let json = json!({
+            "protection": {
+                "macs": {
+                    "browser": { "chat_ip_eligibility_status": "0123ABCD" }
+                }
+            }
+        });
+        assert!(is_mac_protected(
+            &json,
+            "browser.chat_ip_eligibility_status"
+        ));
+        assert!(!is_mac_protected(&json, "browser.show_home_button"));
+        assert!(!is_mac_protected(
+            &json!({}),
+            "browser.chat_ip_eligibility_status"
+        ));
+    }
+}
@@ -1,98 +1,183 @@
 use anyhow::{Context, Result};
 use std::fs;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
-use crate::constants::{APP_LABEL, paths};
+use super::manifest::{FileKind, Manifest};
+use super::render::{generate_desktop_entry, generate_unit_file};
+use super::steps::InstallSteps;
+use super::{InstallOptions, UninstallOptions, remove_data, stop_running_instance};
+use crate::constants::{APP_LABEL, BINARY_NAME, edge::USER_DATA_PATHS, paths};
+use crate::error::Error;
+use crate::metrics::MetricsOptions;
 
-pub fn install() -> Result<()> {
-    log::info!("Installing Edge Copilot Helper...");
+/// Linux 上的自启动方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[non_exhaustive]
+pub enum InstallMethod {
+    /// systemd user service
+    Systemd,
+    /// ~/.config/autostart/*.desktop entry, for sessions without systemd
+    XdgAutostart,
+    /// Install the binary only, without autostart
+    None,
+}
+
+pub fn install(options: &InstallOptions) -> Result<()> {
+    if options.system {
+        return install_system(options);
+    }
 
     let current_exe = std::env::current_exe().context("Failed to get current executable path")?;
     let install_dir = paths::install_dir();
     let log_dir = paths::log_dir();
-    let unit_path = paths::unit_path();
     let binary_path = paths::binary_path();
 
+    // 0. Choose install method
+    let systemd_available = systemd_user_available();
+    let method = match options.method {
+        Some(InstallMethod::Systemd) if !systemd_available => {
+            anyhow::bail!(
+                "systemd user session is not available; use --method=xdg-autostart or --method=none"
+            );
+        }
+        Some(method) => method,
+        None if systemd_available => InstallMethod::Systemd,
+        None => {
+            if !options.print_unit {
+                log::warn!(
+                    "⚠️ systemd user session is not available, falling back to XDG autostart."
+                );
+            }
+            InstallMethod::XdgAutostart
+        }
+    };
+
+    if options.print_unit {
+        match method {
+            InstallMethod::Systemd => {
+                println!(
+                    "{}",
+                    render_unit_file(&binary_path, &install_dir, &options.metrics)?
+                )
+            }
+            InstallMethod::XdgAutostart => println!("{}", generate_desktop_entry(&binary_path)),
+            InstallMethod::None => log::info!("--method=none installs no service file."),
+        }
+        return Ok(());
+    }
+
+    let mut steps = InstallSteps::new(options.dry_run);
+    steps.step("Installing Edge Copilot Helper...");
+    if steps.is_dry_run()
+        && let Some(name) = clap::ValueEnum::to_possible_value(&method)
+    {
+        log::info!("[dry-run] Install method: {}", name.get_name());
+    }
+
     // 1. Create directories
-    log::info!("Creating directories...");
-    fs::create_dir_all(&install_dir).with_context(|| {
-        format!(
-            "Failed to create install directory: {}",
-            install_dir.display()
-        )
-    })?;
-    fs::create_dir_all(&log_dir)
-        .with_context(|| format!("Failed to create log directory: {}", log_dir.display()))?;
+    steps.step("Creating directories...");
+    steps.create_dir_all(&install_dir)?;
+    steps.create_dir_all(&log_dir)?;
 
-    // Ensure systemd user directory exists
-    if let Some(parent) = unit_path.parent() {
-        fs::create_dir_all(parent).with_context(|| {
-            format!(
-                "Failed to create systemd user directory: {}",
-                parent.display()
-            )
-        })?;
+    // 2. Stop whatever a previous install left running (possibly via another method)
+    steps.step("Checking for existing service...");
+    if systemd_available {
+        steps.run_quiet("systemctl", &["--user", "stop", APP_LABEL]);
+        steps.run_quiet("systemctl", &["--user", "disable", APP_LABEL]);
     }
+    steps.remove_file(&paths::autostart_path())?;
+    steps.action(&format!("stop the running {BINARY_NAME} instance"), || {
+        stop_running_instance();
+        Ok(())
+    })?;
 
-    // 2. Copy binary
-    log::info!("Installing binary...");
-    fs::copy(&current_exe, &binary_path)
-        .with_context(|| format!("Failed to copy binary to {}", binary_path.display()))?;
+    // 3. Copy binary
+    steps.step("Installing binary...");
+    steps.install_binary(&current_exe, &binary_path)?;
 
-    // Make executable
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = fs::metadata(&binary_path)?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&binary_path, perms)?;
+    // 4. Configure autostart
+    match method {
+        InstallMethod::Systemd => {
+            install_systemd(&mut steps, &binary_path, &install_dir, &options.metrics)?
+        }
+        InstallMethod::XdgAutostart => install_autostart(&mut steps, &binary_path)?,
+        InstallMethod::None => {
+            steps.save_manifest()?;
+            if steps.is_dry_run() {
+                return Ok(());
+            }
+            log::info!("");
+            log::info!("Binary installed without autostart.");
+            log::info!("  Binary: {}", binary_path.display());
+            log::info!("");
+            log::info!("Start it manually with: {} daemon", binary_path.display());
+        }
     }
 
-    // 3. Stop existing service if present
-    log::info!("Checking for existing service...");
-    let _ = Command::new("systemctl")
-        .args(["--user", "stop", APP_LABEL])
-        .output();
-    let _ = Command::new("systemctl")
-        .args(["--user", "disable", APP_LABEL])
-        .output();
+    Ok(())
+}
 
-    // 4. Generate and write unit file
-    log::info!("Creating systemd unit file...");
-    let unit_content = generate_unit_file(&binary_path);
-    fs::write(&unit_path, unit_content)
-        .with_context(|| format!("Failed to write unit file to {}", unit_path.display()))?;
+/// 渲染当前用户的 systemd unit
+///
+/// 列出所有 Edge 渠道的用户数据目录（包括尚未安装的渠道），之后安装的 Edge 也能写入。
+fn render_unit_file(
+    binary_path: &Path,
+    install_dir: &Path,
+    metrics: &MetricsOptions,
+) -> Result<String> {
+    let home = dirs::home_dir().context("Could not determine home directory")?;
+    let edge_dirs: Vec<PathBuf> = USER_DATA_PATHS.iter().map(|p| home.join(p)).collect();
+    Ok(generate_unit_file(
+        binary_path,
+        install_dir,
+        &edge_dirs,
+        metrics,
+    ))
+}
 
-    // 5. Reload systemd
-    log::info!("Reloading systemd...");
-    let status = Command::new("systemctl")
-        .args(["--user", "daemon-reload"])
-        .status()
-        .context("Failed to execute systemctl daemon-reload")?;
+/// 安装并启动 systemd user service
+fn install_systemd(
+    steps: &mut InstallSteps,
+    binary_path: &Path,
+    install_dir: &Path,
+    metrics: &MetricsOptions,
+) -> Result<()> {
+    let unit_path = paths::unit_path();
 
-    if !status.success() {
-        anyhow::bail!("Failed to reload systemd daemon");
+    // Ensure systemd user directory exists
+    if let Some(parent) = unit_path.parent() {
+        steps.create_dir_all(parent)?;
     }
 
-    // 6. Enable and start service
-    log::info!("Enabling service...");
-    let status = Command::new("systemctl")
-        .args(["--user", "enable", APP_LABEL])
-        .status()
-        .context("Failed to execute systemctl enable")?;
+    // Generate and write unit file
+    steps.step("Creating systemd unit file...");
+    let unit_content = render_unit_file(binary_path, install_dir, metrics)?;
+    steps.write_file(FileKind::SystemdUnit, &unit_path, &unit_content)?;
+    steps.record_metrics(metrics);
+    steps.save_manifest()?;
 
-    if !status.success() {
-        anyhow::bail!("Failed to enable service");
+    // Reload systemd
+    steps.step("Reloading systemd...");
+    if !steps.run("systemctl", &["--user", "daemon-reload"])? {
+        anyhow::bail!(Error::ServiceManager(
+            "Failed to reload systemd daemon".into()
+        ));
     }
 
-    log::info!("Starting service...");
-    let status = Command::new("systemctl")
-        .args(["--user", "start", APP_LABEL])
-        .status()
-        .context("Failed to execute systemctl start")?;
+    // Enable and start service
+    steps.step("Enabling service...");
+    if !steps.run("systemctl", &["--user", "enable", APP_LABEL])? {
+        anyhow::bail!(Error::ServiceManager("Failed to enable service".into()));
+    }
 
-    if !status.success() {
-        anyhow::bail!("Failed to start service");
+    steps.step("Starting service...");
+    if !steps.run("systemctl", &["--user", "start", APP_LABEL])? {
+        anyhow::bail!(Error::ServiceManager("Failed to start service".into()));
+    }
+
+    if steps.is_dry_run() {
+        return Ok(());
     }
 
     log::info!("");
@@ -109,65 +194,383 @@ pub fn install() -> Result<()> {
     Ok(())
 }
 
-pub fn uninstall() -> Result<()> {
+/// 写入 XDG autostart 条目并立即在后台启动一次
+fn install_autostart(steps: &mut InstallSteps, binary_path: &Path) -> Result<()> {
+    let autostart_path = paths::autostart_path();
+
+    if let Some(parent) = autostart_path.parent() {
+        steps.create_dir_all(parent)?;
+    }
+
+    steps.step("Creating XDG autostart entry...");
+    steps.write_file(
+        FileKind::DesktopEntry,
+        &autostart_path,
+        &generate_desktop_entry(binary_path),
+    )?;
+    steps.save_manifest()?;
+
+    steps.step("Starting daemon...");
+    steps.action(&format!("$ {} daemon &", binary_path.display()), || {
+        spawn_daemon(binary_path)
+    })?;
+
+    if steps.is_dry_run() {
+        return Ok(());
+    }
+
+    log::info!("");
+    log::info!("Autostart entry installed and daemon started successfully!");
+    log::info!("  Binary:    {}", binary_path.display());
+    log::info!("  Autostart: {}", autostart_path.display());
+    log::info!("  Logs:      {}", paths::log_dir().display());
+    log::info!("");
+    log::info!("The helper will start automatically with your desktop session.");
+
+    Ok(())
+}
+
+pub fn uninstall(options: &UninstallOptions) -> Result<()> {
+    if options.system {
+        return uninstall_system(options);
+    }
+
     log::info!("Uninstalling Edge Copilot Helper...");
 
-    let install_dir = paths::install_dir();
-    let unit_path = paths::unit_path();
+    let manifest = match Manifest::load()? {
+        Some(manifest) => manifest,
+        None => {
+            log::warn!("⚠️ No install manifest found, removing known install locations.");
+            let mut manifest = Manifest::new();
+            manifest.add_legacy_file(FileKind::Binary, paths::binary_path());
+            manifest.add_legacy_file(FileKind::SystemdUnit, paths::unit_path());
+            manifest.add_legacy_file(FileKind::DesktopEntry, paths::autostart_path());
+            manifest
+        }
+    };
 
     // 1. Stop and disable service
-    log::info!("Stopping service...");
-    let _ = Command::new("systemctl")
-        .args(["--user", "stop", APP_LABEL])
-        .output();
+    if manifest.has(FileKind::SystemdUnit) {
+        stop_systemd_service();
+    }
 
-    log::info!("Disabling service...");
-    let _ = Command::new("systemctl")
-        .args(["--user", "disable", APP_LABEL])
-        .output();
+    // 2. Remove installed files (binary, unit file, autostart entry)
+    manifest.remove_files()?;
+
+    // 3. Reload systemd / stop the daemon started by the autostart entry
+    if manifest.has(FileKind::SystemdUnit) {
+        let _ = Command::new("systemctl")
+            .args(["--user", "daemon-reload"])
+            .output();
+    }
+    if manifest.has(FileKind::DesktopEntry) {
+        stop_running_instance();
+    }
+
+    // 4. Remove logs, backups and runtime files
+    remove_data(options)?;
+
+    log::info!("");
+    log::info!("Uninstallation complete.");
+
+    Ok(())
+}
+
+/// 为所有用户安装：二进制放在 `/usr/local/bin`，user unit 放在 `/etc/systemd/user`
+/// 并通过 `systemctl --global enable` 对每个用户生效
+///
+/// 每个用户的实例在各自的用户管理器中运行，日志和锁文件仍位于各自的
+/// `~/.local/share` 下。
+fn install_system(options: &InstallOptions) -> Result<()> {
+    let current_exe = std::env::current_exe().context("Failed to get current executable path")?;
+    let binary_path = paths::system::binary_path();
+    let unit_path = paths::system::unit_path();
+    let unit_content = render_system_unit_file(&binary_path, &options.metrics);
 
-    // 2. Remove unit file
-    if unit_path.exists() {
-        log::info!("Removing unit file: {}", unit_path.display());
-        fs::remove_file(&unit_path)
-            .with_context(|| format!("Failed to remove {}", unit_path.display()))?;
+    if options.print_unit {
+        println!("{unit_content}");
+        return Ok(());
     }
 
-    // 3. Reload systemd
+    if !options.dry_run && !is_root() {
+        anyhow::bail!("--system requires root; re-run with sudo");
+    }
+
+    let mut steps = InstallSteps::new(options.dry_run).with_manifest_path(Manifest::system_path());
+    steps.step("Installing Edge Copilot Helper for all users...");
+
+    // 1. Create directories
+    steps.step("Creating directories...");
+    steps.create_dir_all(&paths::system::data_dir())?;
+    if let Some(parent) = unit_path.parent() {
+        steps.create_dir_all(parent)?;
+    }
+
+    // 2. Copy binary（先删除旧文件，运行中的用户实例继续使用已打开的旧 inode）
+    steps.step("Installing binary...");
+    steps.remove_file(&binary_path)?;
+    steps.install_binary(&current_exe, &binary_path)?;
+
+    // 3. Write unit file
+    steps.step("Creating systemd user unit...");
+    steps.write_file(FileKind::SystemdUnit, &unit_path, &unit_content)?;
+    steps.save_manifest()?;
+
+    // 4. Enable for every user
+    steps.step("Enabling service for all users...");
+    if !steps.run("systemctl", &["--global", "enable", APP_LABEL])? {
+        anyhow::bail!(Error::ServiceManager(
+            "Failed to enable service globally".into()
+        ));
+    }
+
+    if steps.is_dry_run() {
+        return Ok(());
+    }
+
+    log::info!("");
+    log::info!("Service installed for all users!");
+    log::info!("  Binary: {}", binary_path.display());
+    log::info!("  Unit:   {}", unit_path.display());
+    log::info!("");
+    log::info!("It starts automatically at each user's next login.");
+    log::info!("Users already logged in can start it now with:");
+    log::info!("  systemctl --user daemon-reload");
+    log::info!("  systemctl --user start {APP_LABEL}");
+
+    Ok(())
+}
+
+/// 渲染系统级 unit：路径使用 `%h`，由每个用户的管理器展开
+fn render_system_unit_file(binary_path: &Path, metrics: &MetricsOptions) -> String {
+    let edge_dirs: Vec<PathBuf> = USER_DATA_PATHS
+        .iter()
+        .map(|p| Path::new("%h").join(p))
+        .collect();
+    generate_unit_file(
+        binary_path,
+        &paths::system::user_install_dir(),
+        &edge_dirs,
+        metrics,
+    )
+}
+
+/// 撤销 `install --system`
+///
+/// 各用户主目录下的日志不会被删除。
+fn uninstall_system(options: &UninstallOptions) -> Result<()> {
+    if !is_root() {
+        anyhow::bail!("--system requires root; re-run with sudo");
+    }
+    if options.revert {
+        log::warn!(
+            "⚠️ --revert is ignored with --system; each user can run `revert` to restore their settings."
+        );
+    }
+
+    log::info!("Uninstalling Edge Copilot Helper for all users...");
+
+    let manifest_path = Manifest::system_path();
+    let manifest = match Manifest::load_from(&manifest_path)? {
+        Some(manifest) => manifest,
+        None => {
+            log::warn!("⚠️ No install manifest found, removing known install locations.");
+            let mut manifest = Manifest::new();
+            manifest.add_legacy_file(FileKind::Binary, paths::system::binary_path());
+            manifest.add_legacy_file(FileKind::SystemdUnit, paths::system::unit_path());
+            manifest
+        }
+    };
+
+    // 1. Disable for every user
+    log::info!("Disabling service for all users...");
     let _ = Command::new("systemctl")
-        .args(["--user", "daemon-reload"])
+        .args(["--global", "disable", APP_LABEL])
         .output();
 
-    // 4. Remove install directory (includes binary and logs)
-    if install_dir.exists() {
-        log::info!("Removing files: {}", install_dir.display());
-        fs::remove_dir_all(&install_dir)
-            .with_context(|| format!("Failed to remove {}", install_dir.display()))?;
+    // 2. Remove installed files (binary, unit file)
+    manifest.remove_files()?;
+
+    // 3. Remove manifest and its directory
+    if manifest_path.exists() {
+        fs::remove_file(&manifest_path)
+            .with_context(|| format!("Failed to remove {}", manifest_path.display()))?;
+    }
+    let data_dir = paths::system::data_dir();
+    if data_dir.exists() {
+        match fs::remove_dir(&data_dir) {
+            Ok(()) => log::info!("Removed directory: {}", data_dir.display()),
+            Err(_) => log::info!("Keeping directory: {}", data_dir.display()),
+        }
     }
 
     log::info!("");
     log::info!("Uninstallation complete.");
+    log::info!("Instances already running stop when each user logs out, or now with:");
+    log::info!("  systemctl --user stop {APP_LABEL}");
 
     Ok(())
 }
 
-fn generate_unit_file(binary_path: &std::path::Path) -> String {
-    let binary_str = binary_path.to_str().unwrap_or("");
+/// 当前进程是否以 root 身份运行
+fn is_root() -> bool {
+    crate::safety::current_uid() == 0
+}
 
-    format!(
-        r#"[Unit]
-Description=Edge Copilot Helper - Bypass Microsoft Edge Copilot region restrictions
-After=default.target
+/// 停止已安装的服务（upgrade 使用）
+///
+/// 按安装清单记录的方式处理；`--method=none` 安装时不停止手动启动的实例。
+pub fn stop_service() {
+    match installed_method() {
+        InstallMethod::Systemd => {
+            let _ = Command::new("systemctl")
+                .args(["--user", "stop", APP_LABEL])
+                .output();
+        }
+        InstallMethod::XdgAutostart => stop_running_instance(),
+        InstallMethod::None => {}
+    }
+}
 
-[Service]
-Type=simple
-ExecStart={binary} daemon
-Restart=always
-RestartSec=5
+/// 启动已安装的服务（upgrade 使用），返回是否启动了 daemon
+///
+/// `--method=none` 安装时不启动任何进程。
+pub fn start_service() -> Result<bool> {
+    match installed_method() {
+        InstallMethod::Systemd => {
+            let status = Command::new("systemctl")
+                .args(["--user", "start", APP_LABEL])
+                .status()
+                .context("Failed to execute systemctl start")?;
 
-[Install]
-WantedBy=default.target
-"#,
-        binary = binary_str
-    )
+            if !status.success() {
+                anyhow::bail!(Error::ServiceManager("Failed to start service".into()));
+            }
+            Ok(true)
+        }
+        InstallMethod::XdgAutostart => spawn_daemon(&paths::binary_path()).map(|_| true),
+        InstallMethod::None => {
+            log::info!(
+                "Installed without autostart; restart any manually started instance to use the new version."
+            );
+            Ok(false)
+        }
+    }
+}
+
+/// 按当前模板重新生成 systemd unit（upgrade 替换二进制时使用）
+///
+/// 使用安装清单记录的指标选项渲染；内容有变化时写入并重新加载 systemd，
+/// 返回原内容供回滚。非 systemd 安装或清单中没有记录选项（较早版本的安装）时不做修改。
+pub fn regenerate_unit() -> Result<Option<String>> {
+    let Some(mut manifest) = Manifest::load()? else {
+        return Ok(None);
+    };
+    if !manifest.has(FileKind::SystemdUnit) {
+        return Ok(None);
+    }
+    let Some(metrics) = manifest.metrics.clone() else {
+        log::info!("Keeping the existing unit file; re-run install to regenerate it.");
+        return Ok(None);
+    };
+
+    let unit_path = paths::unit_path();
+    let previous = fs::read_to_string(&unit_path)
+        .with_context(|| format!("Failed to read {}", unit_path.display()))?;
+    let content = render_unit_file(&paths::binary_path(), &paths::install_dir(), &metrics)?;
+    if content == previous {
+        return Ok(None);
+    }
+
+    log::info!("Regenerating unit file: {}", unit_path.display());
+    fs::write(&unit_path, &content)
+        .with_context(|| format!("Failed to write {}", unit_path.display()))?;
+    manifest.update_file(FileKind::SystemdUnit, &unit_path)?;
+    manifest.save()?;
+    reload_systemd()?;
+    Ok(Some(previous))
+}
+
+/// 写回 [`regenerate_unit`] 替换前的 unit（upgrade 回滚时使用）
+pub fn restore_unit(previous: &str) -> Result<()> {
+    let unit_path = paths::unit_path();
+    fs::write(&unit_path, previous)
+        .with_context(|| format!("Failed to restore {}", unit_path.display()))?;
+    if let Some(mut manifest) = Manifest::load()? {
+        manifest.update_file(FileKind::SystemdUnit, &unit_path)?;
+        manifest.save()?;
+    }
+    reload_systemd()
+}
+
+fn reload_systemd() -> Result<()> {
+    let status = Command::new("systemctl")
+        .args(["--user", "daemon-reload"])
+        .status()
+        .context("Failed to execute systemctl daemon-reload")?;
+    if !status.success() {
+        anyhow::bail!(Error::ServiceManager(
+            "Failed to reload systemd daemon".into()
+        ));
+    }
+    Ok(())
+}
+
+/// 当前安装使用的自启动方式，根据安装清单中记录的文件判断
+///
+/// 没有清单的旧版本安装按已知位置上存在的文件判断。
+fn installed_method() -> InstallMethod {
+    let (systemd, autostart) = match Manifest::load() {
+        Ok(Some(manifest)) => (
+            manifest.has(FileKind::SystemdUnit),
+            manifest.has(FileKind::DesktopEntry),
+        ),
+        _ => (
+            paths::unit_path().exists(),
+            paths::autostart_path().exists(),
+        ),
+    };
+    if systemd {
+        InstallMethod::Systemd
+    } else if autostart {
+        InstallMethod::XdgAutostart
+    } else {
+        InstallMethod::None
+    }
+}
+
+/// 在后台启动 daemon（脱离当前终端）
+fn spawn_daemon(binary_path: &Path) -> Result<()> {
+    Command::new(binary_path)
+        .arg("daemon")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to start daemon")?;
+    Ok(())
+}
+
+/// 检测 systemd 用户会话是否可用
+///
+/// WSL、容器以及使用 OpenRC / runit 的发行版上 `systemctl --user` 无法连接用户管理器。
+fn systemd_user_available() -> bool {
+    Command::new("systemctl")
+        .args(["--user", "show-environment"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+fn stop_systemd_service() {
+    log::info!("Stopping service...");
+    let _ = Command::new("systemctl")
+        .args(["--user", "stop", APP_LABEL])
+        .output();
+
+    log::info!("Disabling service...");
+    let _ = Command::new("systemctl")
+        .args(["--user", "disable", APP_LABEL])
+        .output();
 }
@@ -1,79 +1,71 @@
 use anyhow::{Context, Result};
-use std::fs;
 use std::process::Command;
 
+use super::manifest::{FileKind, Manifest};
+use super::render::generate_plist;
+use super::steps::InstallSteps;
+use super::{InstallOptions, UninstallOptions, remove_data};
 use crate::constants::{APP_LABEL, paths};
+use crate::error::Error;
 
-pub fn install() -> Result<()> {
-    log::info!("Installing Edge Copilot Helper...");
-
+pub fn install(options: &InstallOptions) -> Result<()> {
     let current_exe = std::env::current_exe().context("Failed to get current executable path")?;
     let install_dir = paths::install_dir();
     let log_dir = paths::log_dir();
     let plist_path = paths::plist_path();
     let binary_path = paths::binary_path();
 
+    let plist_content = generate_plist(&binary_path, &log_dir);
+    if options.print_unit {
+        println!("{plist_content}");
+        return Ok(());
+    }
+
+    let mut steps = InstallSteps::new(options.dry_run);
+    steps.step("Installing Edge Copilot Helper...");
+
     // 1. Create directories
-    log::info!("Creating directories...");
-    fs::create_dir_all(&install_dir).with_context(|| {
-        format!(
-            "Failed to create install directory: {}",
-            install_dir.display()
-        )
-    })?;
-    fs::create_dir_all(&log_dir)
-        .with_context(|| format!("Failed to create log directory: {}", log_dir.display()))?;
+    steps.step("Creating directories...");
+    steps.create_dir_all(&install_dir)?;
+    steps.create_dir_all(&log_dir)?;
 
     // Ensure LaunchAgents directory exists
     if let Some(parent) = plist_path.parent() {
-        fs::create_dir_all(parent).with_context(|| {
-            format!(
-                "Failed to create LaunchAgents directory: {}",
-                parent.display()
-            )
-        })?;
+        steps.create_dir_all(parent)?;
     }
 
     // 2. Copy binary
-    log::info!("Installing binary...");
-    fs::copy(&current_exe, &binary_path)
-        .with_context(|| format!("Failed to copy binary to {}", binary_path.display()))?;
-
-    // Make executable (should already be, but ensure it)
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = fs::metadata(&binary_path)?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&binary_path, perms)?;
-    }
+    steps.step("Installing binary...");
+    steps.install_binary(&current_exe, &binary_path)?;
 
     // 3. Unload existing service if present
-    log::info!("Checking for existing service...");
+    steps.step("Checking for existing service...");
     let uid = get_uid();
-    let _ = Command::new("launchctl")
-        .args([
+    steps.run_quiet(
+        "launchctl",
+        &[
             "bootout",
             &format!("gui/{uid}"),
             plist_path.to_str().unwrap_or(""),
-        ])
-        .output();
+        ],
+    );
 
     // 4. Generate and write plist
-    log::info!("Creating Launch Agent plist...");
-    let plist_content = generate_plist(&binary_path, &log_dir);
-    fs::write(&plist_path, plist_content)
-        .with_context(|| format!("Failed to write plist to {}", plist_path.display()))?;
+    steps.step("Creating Launch Agent plist...");
+    steps.write_file(FileKind::LaunchAgent, &plist_path, &plist_content)?;
+    steps.save_manifest()?;
 
     // 5. Load service
-    log::info!("Loading service...");
-    let status = Command::new("launchctl")
-        .args(["load", "-w", plist_path.to_str().unwrap_or("")])
-        .status()
-        .context("Failed to execute launchctl load")?;
+    steps.step("Loading service...");
+    if !steps.run(
+        "launchctl",
+        &["load", "-w", plist_path.to_str().unwrap_or("")],
+    )? {
+        anyhow::bail!(Error::ServiceManager("Failed to load Launch Agent".into()));
+    }
 
-    if !status.success() {
-        anyhow::bail!("Failed to load Launch Agent");
+    if steps.is_dry_run() {
+        return Ok(());
     }
 
     log::info!("");
@@ -86,12 +78,20 @@ pub fn install() -> Result<()> {
     Ok(())
 }
 
-pub fn uninstall() -> Result<()> {
+pub fn uninstall(options: &UninstallOptions) -> Result<()> {
     log::info!("Uninstalling Edge Copilot Helper...");
 
-    let install_dir = paths::install_dir();
-    let log_dir = paths::log_dir();
     let plist_path = paths::plist_path();
+    let manifest = match Manifest::load()? {
+        Some(manifest) => manifest,
+        None => {
+            log::warn!("⚠️ No install manifest found, removing known install locations.");
+            let mut manifest = Manifest::new();
+            manifest.add_legacy_file(FileKind::Binary, paths::binary_path());
+            manifest.add_legacy_file(FileKind::LaunchAgent, plist_path.clone());
+            manifest
+        }
+    };
 
     // 1. Unload service
     log::info!("Stopping service...");
@@ -104,26 +104,11 @@ pub fn uninstall() -> Result<()> {
         ])
         .output();
 
-    // 2. Remove plist
-    if plist_path.exists() {
-        log::info!("Removing plist: {}", plist_path.display());
-        fs::remove_file(&plist_path)
-            .with_context(|| format!("Failed to remove {}", plist_path.display()))?;
-    }
-
-    // 3. Remove install directory
-    if install_dir.exists() {
-        log::info!("Removing files: {}", install_dir.display());
-        fs::remove_dir_all(&install_dir)
-            .with_context(|| format!("Failed to remove {}", install_dir.display()))?;
-    }
+    // 2. Remove installed files (binary, plist)
+    manifest.remove_files()?;
 
-    // 4. Remove logs
-    if log_dir.exists() {
-        log::info!("Removing logs: {}", log_dir.display());
-        fs::remove_dir_all(&log_dir)
-            .with_context(|| format!("Failed to remove {}", log_dir.display()))?;
-    }
+    // 3. Remove logs, backups and runtime files
+    remove_data(options)?;
 
     log::info!("");
     log::info!("Uninstallation complete.");
@@ -131,6 +116,31 @@ pub fn uninstall() -> Result<()> {
     Ok(())
 }
 
+/// 停止已安装的服务（upgrade 使用）
+pub fn stop_service() {
+    let uid = get_uid();
+    let _ = Command::new("launchctl")
+        .args([
+            "bootout",
+            &format!("gui/{uid}"),
+            paths::plist_path().to_str().unwrap_or(""),
+        ])
+        .output();
+}
+
+/// 启动已安装的服务（upgrade 使用）
+pub fn start_service() -> Result<()> {
+    let status = Command::new("launchctl")
+        .args(["load", "-w", paths::plist_path().to_str().unwrap_or("")])
+        .status()
+        .context("Failed to execute launchctl load")?;
+
+    if !status.success() {
+        anyhow::bail!(Error::ServiceManager("Failed to load Launch Agent".into()));
+    }
+    Ok(())
+}
+
 fn get_uid() -> String {
     Command::new("id")
         .arg("-u")
@@ -138,38 +148,3 @@ fn get_uid() -> String {
         .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
         .unwrap_or_else(|_| "501".to_string())
 }
-
-fn generate_plist(binary_path: &std::path::Path, log_dir: &std::path::Path) -> String {
-    let binary_str = binary_path.to_str().unwrap_or("");
-    let stdout_log = log_dir.join("service.log");
-    let stderr_log = log_dir.join("service.err");
-
-    format!(
-        r#"<?xml version="1.0" encoding="UTF-8"?>
-<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
-<plist version="1.0">
-<dict>
-    <key>Label</key>
-    <string>{label}</string>
-    <key>ProgramArguments</key>
-    <array>
-        <string>{binary}</string>
-        <string>daemon</string>
-    </array>
-    <key>RunAtLoad</key>
-    <true/>
-    <key>KeepAlive</key>
-    <true/>
-    <key>StandardOutPath</key>
-    <string>{stdout}</string>
-    <key>StandardErrorPath</key>
-    <string>{stderr}</string>
-</dict>
-</plist>
-"#,
-        label = APP_LABEL,
-        binary = binary_str,
-        stdout = stdout_log.to_str().unwrap_or(""),
-        stderr = stderr_log.to_str().unwrap_or("")
-    )
-}
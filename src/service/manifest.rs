@@ -0,0 +1,252 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::constants::paths;
+use crate::metrics::MetricsOptions;
+
+/// 清单文件名（位于安装目录下）
+const MANIFEST_FILE: &str = "install-manifest.json";
+
+/// install 创建的文件类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileKind {
+    /// 安装后的可执行文件
+    Binary,
+    /// systemd user unit
+    SystemdUnit,
+    /// XDG autostart `.desktop` 条目
+    DesktopEntry,
+    /// macOS LaunchAgent plist
+    LaunchAgent,
+}
+
+/// 清单中记录的单个文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub kind: FileKind,
+    pub path: PathBuf,
+    /// 安装时的 SHA-256；旧版本安装（无清单）推断出的条目没有校验和
+    pub sha256: Option<String>,
+}
+
+/// 清单中记录的单个注册表值
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryEntry {
+    pub key: String,
+    pub value: String,
+    pub data: String,
+}
+
+/// install 创建的全部产物，uninstall 据此精确删除
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: String,
+    pub installed_at: String,
+    #[serde(default)]
+    pub files: Vec<FileEntry>,
+    #[serde(default)]
+    pub registry: Vec<RegistryEntry>,
+    /// 写入 systemd unit 的指标选项，upgrade 重新生成 unit 时使用；
+    /// 记录这一项之前的安装没有该字段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<MetricsOptions>,
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            installed_at: chrono::Local::now().to_rfc3339(),
+            files: Vec::new(),
+            registry: Vec::new(),
+            metrics: None,
+        }
+    }
+
+    /// 清单文件路径
+    pub fn path() -> PathBuf {
+        paths::install_dir().join(MANIFEST_FILE)
+    }
+
+    /// 系统级安装的清单文件路径
+    #[cfg(target_os = "linux")]
+    pub fn system_path() -> PathBuf {
+        paths::system::data_dir().join(MANIFEST_FILE)
+    }
+
+    /// 读取已有清单，不存在时返回 `None`
+    pub fn load() -> Result<Option<Self>> {
+        Self::load_from(&Self::path())
+    }
+
+    /// 从指定路径读取清单，不存在时返回 `None`
+    pub fn load_from(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let manifest = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse install manifest at {}", path.display()))?;
+        Ok(Some(manifest))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::path())
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content)
+            .with_context(|| format!("Failed to write install manifest to {}", path.display()))
+    }
+
+    /// 记录一个已写入的文件及其校验和
+    pub fn add_file(&mut self, kind: FileKind, path: &Path) -> Result<()> {
+        let sha256 = file_sha256(path)?;
+        self.files.push(FileEntry {
+            kind,
+            path: path.to_path_buf(),
+            sha256: Some(sha256),
+        });
+        Ok(())
+    }
+
+    /// 更新某一类文件的校验和（如 upgrade 替换二进制后）
+    pub fn update_file(&mut self, kind: FileKind, path: &Path) -> Result<()> {
+        self.files.retain(|f| f.kind != kind);
+        self.add_file(kind, path)
+    }
+
+    /// 记录一个未知校验和的文件（用于旧版本安装的兼容处理）
+    pub fn add_legacy_file(&mut self, kind: FileKind, path: PathBuf) {
+        if path.exists() {
+            self.files.push(FileEntry {
+                kind,
+                path,
+                sha256: None,
+            });
+        }
+    }
+
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    pub fn add_registry(&mut self, key: &str, value: &str, data: &str) {
+        self.registry.push(RegistryEntry {
+            key: key.to_string(),
+            value: value.to_string(),
+            data: data.to_string(),
+        });
+    }
+
+    /// 是否包含某一类文件
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn has(&self, kind: FileKind) -> bool {
+        self.files.iter().any(|f| f.kind == kind)
+    }
+
+    /// 删除清单中记录的文件
+    ///
+    /// 内容与安装时不一致的文件视为已被用户修改，保留并给出警告。
+    pub fn remove_files(&self) -> Result<()> {
+        remove_entries(self.files.iter())
+    }
+
+    /// 本清单中记录、但 `current` 中没有的文件（重新安装时换了自启动方式留下的旧产物）
+    pub fn stale_files<'a>(&'a self, current: &'a Manifest) -> impl Iterator<Item = &'a FileEntry> {
+        self.files
+            .iter()
+            .filter(|entry| !current.files.iter().any(|f| f.path == entry.path))
+    }
+
+    /// 删除 [`stale_files`](Self::stale_files) 列出的文件
+    pub fn remove_stale_files(&self, current: &Manifest) -> Result<()> {
+        remove_entries(self.stale_files(current))
+    }
+}
+
+/// 删除清单条目对应的文件，内容与安装时不一致的文件保留并给出警告
+fn remove_entries<'a>(entries: impl Iterator<Item = &'a FileEntry>) -> Result<()> {
+    for entry in entries {
+        if !entry.path.exists() {
+            continue;
+        }
+
+        if let Some(expected) = &entry.sha256 {
+            let actual = file_sha256(&entry.path)?;
+            if &actual != expected {
+                log::warn!(
+                    "⚠️ Keeping {} (modified since install)",
+                    entry.path.display()
+                );
+                continue;
+            }
+        }
+
+        log::info!("Removing file: {}", entry.path.display());
+        fs::remove_file(&entry.path)
+            .with_context(|| format!("Failed to remove {}", entry.path.display()))?;
+    }
+    Ok(())
+}
+
+fn file_sha256(path: &Path) -> Result<String> {
+    let content = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let digest = Sha256::digest(&content);
+    Ok(digest.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let mut manifest = Manifest::new();
+        manifest.files.push(FileEntry {
+            kind: FileKind::SystemdUnit,
+            path: PathBuf::from("/home/u/.config/systemd/user/app.service"),
+            sha256: Some("00".repeat(32)),
+        });
+        manifest.add_registry("HKCU\\Run", "App", "\"C:\\app.exe\" daemon");
+
+        let json = serde_json::to_string(&manifest).unwrap();
+        assert!(json.contains("\"kind\":\"systemd_unit\""));
+
+        let parsed: Manifest = serde_json::from_str(&json).unwrap();
+        assert!(parsed.has(FileKind::SystemdUnit));
+        assert!(!parsed.has(FileKind::Binary));
+        assert_eq!(parsed.registry[0].value, "App");
+    }
+
+    #[test]
+    fn test_remove_stale_files_keeps_reinstalled_and_modified_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("app");
+        let unit = dir.path().join("app.service");
+        let edited = dir.path().join("app.desktop");
+        for path in [&binary, &unit, &edited] {
+            fs::write(path, "old").unwrap();
+        }
+
+        let mut previous = Manifest::new();
+        previous.add_file(FileKind::Binary, &binary).unwrap();
+        previous.add_file(FileKind::SystemdUnit, &unit).unwrap();
+        previous.add_file(FileKind::DesktopEntry, &edited).unwrap();
+        fs::write(&edited, "edited by the user").unwrap();
+
+        // 以 --method=none 重新安装：只剩二进制
+        let mut current = Manifest::new();
+        current.add_file(FileKind::Binary, &binary).unwrap();
+
+        assert_eq!(previous.stale_files(&current).count(), 2);
+        previous.remove_stale_files(&current).unwrap();
+        assert!(binary.exists());
+        assert!(!unit.exists());
+        assert!(edited.exists());
+    }
+}
@@ -2,41 +2,212 @@
 mod linux;
 #[cfg(target_os = "macos")]
 mod macos;
+mod manifest;
+/// 各平台 unit / plist / 注册表命令的渲染函数，不区分编译平台以便预览和测试
+pub mod render;
+mod steps;
+mod upgrade;
 #[cfg(target_os = "windows")]
 mod windows;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::fs;
 
-pub fn install() -> Result<()> {
+use crate::constants::{LOCK_FILE, LOCK_INFO_FILE, STATE_FILE, paths};
+
+#[cfg(target_os = "linux")]
+pub use linux::InstallMethod;
+pub use upgrade::{UpgradeOptions, upgrade};
+
+/// install 命令的选项
+#[derive(Debug, Clone, Default)]
+pub struct InstallOptions {
+    /// 自启动方式（仅 Linux），未指定时自动检测
+    #[cfg(target_os = "linux")]
+    pub method: Option<InstallMethod>,
+    /// 为所有用户安装（仅 Linux，需要 root）
+    #[cfg(target_os = "linux")]
+    pub system: bool,
+    /// 服务导出的 Prometheus 指标（仅 Linux systemd，写入 unit 的启动参数并放开对应的沙箱限制）
+    #[cfg(target_os = "linux")]
+    pub metrics: crate::metrics::MetricsOptions,
+    /// 只打印将要执行的步骤和生成的文件内容，不做任何修改
+    pub dry_run: bool,
+    /// 只输出渲染后的 unit / plist / 注册表命令
+    pub print_unit: bool,
+}
+
+/// uninstall 命令的选项
+#[derive(Debug, Clone, Default)]
+pub struct UninstallOptions {
+    /// 保留日志目录
+    pub keep_logs: bool,
+    /// 保留配置文件备份目录
+    pub keep_backups: bool,
+    /// 卸载前撤销修复，恢复 Edge 的原始设置
+    pub revert: bool,
+    /// 卸载系统级安装（仅 Linux，需要 root）
+    #[cfg(target_os = "linux")]
+    pub system: bool,
+}
+
+pub fn install(options: &InstallOptions) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        macos::install(options)
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        windows::install(options)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        linux::install(options)
+    }
+}
+
+pub fn uninstall(options: &UninstallOptions) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        macos::uninstall(options)
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        windows::uninstall(options)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        linux::uninstall(options)
+    }
+}
+
+/// 停止已安装的服务（失败时忽略，服务可能本就未运行）
+fn stop_service() {
     #[cfg(target_os = "macos")]
     {
-        macos::install()
+        macos::stop_service()
     }
 
     #[cfg(target_os = "windows")]
     {
-        windows::install()
+        windows::stop_service()
     }
 
     #[cfg(target_os = "linux")]
     {
-        linux::install()
+        linux::stop_service()
+    }
+}
+
+/// 请求正在运行的实例（单实例锁的持有者）退出并等待其释放锁；失败时只记录警告
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+fn stop_running_instance() {
+    if let Err(e) = crate::lock::stop_running_instance() {
+        log::warn!("⚠️ {e:#}");
     }
 }
 
-pub fn uninstall() -> Result<()> {
+/// 启动已安装的服务，返回是否启动了 daemon（Linux 上 `--method=none` 安装时不启动）
+fn start_service() -> Result<bool> {
     #[cfg(target_os = "macos")]
     {
-        macos::uninstall()
+        macos::start_service().map(|_| true)
     }
 
     #[cfg(target_os = "windows")]
     {
-        windows::uninstall()
+        windows::start_service().map(|_| true)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        linux::start_service()
     }
+}
+
+/// 按当前版本的模板重新生成服务文件（Linux systemd unit），返回原内容供回滚
+fn regenerate_service_files() -> Result<Option<String>> {
+    #[cfg(target_os = "linux")]
+    {
+        linux::regenerate_unit()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        Ok(None)
+    }
+}
 
+/// 写回 [`regenerate_service_files`] 替换前的内容
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+fn restore_service_files(previous: &str) -> Result<()> {
     #[cfg(target_os = "linux")]
     {
-        linux::uninstall()
+        linux::restore_unit(previous)
     }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        Ok(())
+    }
+}
+
+/// 删除运行期间产生的数据（日志、备份、锁文件、状态数据库、安装清单）
+///
+/// 在服务停止后调用；指定 `revert` 时先根据状态数据库撤销修复。
+/// 安装目录只在清空后才删除，不会连带删除清单之外的文件。
+fn remove_data(options: &UninstallOptions) -> Result<()> {
+    let install_dir = paths::install_dir();
+    let state_path = install_dir.join(STATE_FILE);
+
+    if options.revert {
+        crate::revert(&crate::RevertOptions::default())?;
+    } else if state_path.exists() {
+        log::info!("Discarding recorded original settings (use --revert to restore them first)");
+    }
+
+    let log_dir = paths::log_dir();
+    let backup_dir = paths::backup_dir();
+
+    if options.keep_logs {
+        log::info!("Keeping logs: {}", log_dir.display());
+    } else if log_dir.exists() {
+        log::info!("Removing logs: {}", log_dir.display());
+        fs::remove_dir_all(&log_dir)
+            .with_context(|| format!("Failed to remove {}", log_dir.display()))?;
+    }
+
+    if options.keep_backups {
+        log::info!("Keeping backups: {}", backup_dir.display());
+    } else if backup_dir.exists() {
+        log::info!("Removing backups: {}", backup_dir.display());
+        fs::remove_dir_all(&backup_dir)
+            .with_context(|| format!("Failed to remove {}", backup_dir.display()))?;
+    }
+
+    for runtime_file in [
+        install_dir.join(LOCK_FILE),
+        install_dir.join(LOCK_INFO_FILE),
+        install_dir.join(format!("{STATE_FILE}.lock")),
+        state_path,
+        manifest::Manifest::path(),
+    ] {
+        if runtime_file.exists() {
+            fs::remove_file(&runtime_file)
+                .with_context(|| format!("Failed to remove {}", runtime_file.display()))?;
+        }
+    }
+
+    if install_dir.exists() {
+        match fs::remove_dir(&install_dir) {
+            Ok(()) => log::info!("Removed directory: {}", install_dir.display()),
+            Err(_) => log::info!("Keeping directory: {}", install_dir.display()),
+        }
+    }
+
+    Ok(())
 }
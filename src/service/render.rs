@@ -0,0 +1,272 @@
+use std::path::{Path, PathBuf};
+
+use crate::constants::APP_LABEL;
+use crate::metrics::MetricsOptions;
+
+/// Windows 开机自启动注册表值名
+pub const REG_KEY_NAME: &str = "EdgeCopilotHelper";
+
+/// Windows 开机自启动注册表路径
+pub const REG_PATH: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+
+/// 生成 XDG autostart `.desktop` 条目内容
+pub fn generate_desktop_entry(binary_path: &Path) -> String {
+    // Exec 字段中的参数需加引号，并转义引号内的特殊字符
+    let binary = binary_path
+        .to_str()
+        .unwrap_or("")
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('`', "\\`")
+        .replace('$', "\\$");
+
+    format!(
+        r#"[Desktop Entry]
+Type=Application
+Name=Edge Copilot Helper
+Comment=Bypass Microsoft Edge Copilot region restrictions
+Exec="{binary}" daemon
+Terminal=false
+NoDisplay=true
+X-GNOME-Autostart-enabled=true
+"#
+    )
+}
+
+/// 生成 systemd user unit 内容
+///
+/// 除 `data_dir`（安装目录，存放锁文件和日志）、`edge_dirs`（Edge 用户数据目录）与
+/// 指标 textfile 所在目录外，整个文件系统只读，并限制可用的地址族与系统调用：
+/// 默认只允许 sd_notify 所需的 AF_UNIX，开启 `--metrics-listen` 时再允许 AF_INET / AF_INET6。
+/// 路径中可以使用 systemd 说明符（如 `%h`），供系统级安装为每个用户展开。
+/// `XDG_DATA_HOME` 固定为 `data_dir` 的上级目录：helper 按它确定安装目录，
+/// 用户管理器的环境与安装时不同也不会写到沙箱之外。
+pub fn generate_unit_file(
+    binary_path: &Path,
+    data_dir: &Path,
+    edge_dirs: &[PathBuf],
+    metrics: &MetricsOptions,
+) -> String {
+    let binary_str = binary_path.to_str().unwrap_or("");
+    let environment = data_dir
+        .parent()
+        .map(|data_home| format!("Environment=\"XDG_DATA_HOME={}\"\n", data_home.display()))
+        .unwrap_or_default();
+    let data_dir = data_dir.display();
+    let textfile_dir = metrics
+        .textfile
+        .as_deref()
+        .and_then(Path::parent)
+        .filter(|dir| !dir.as_os_str().is_empty());
+    // "-" 前缀：目录不存在时忽略而不是启动失败（如尚未安装的 Edge 渠道）
+    let read_write_paths = std::iter::once(format!("\"{data_dir}\""))
+        .chain(
+            edge_dirs
+                .iter()
+                .map(PathBuf::as_path)
+                .chain(textfile_dir)
+                .map(|p| format!("\"-{}\"", p.display())),
+        )
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut args = String::from(" daemon");
+    if let Some(textfile) = &metrics.textfile {
+        args.push_str(&format!(" --metrics-textfile \"{}\"", textfile.display()));
+    }
+    if let Some(listen) = metrics.listen {
+        args.push_str(&format!(" --metrics-listen {listen}"));
+    }
+    let address_families = if metrics.listen.is_some() {
+        "AF_UNIX AF_INET AF_INET6"
+    } else {
+        "AF_UNIX"
+    };
+
+    format!(
+        r#"[Unit]
+Description=Edge Copilot Helper - Bypass Microsoft Edge Copilot region restrictions
+After=default.target
+
+[Service]
+Type=notify
+NotifyAccess=main
+{environment}# "+" runs outside the sandbox so the writable data directory exists
+ExecStartPre=+/bin/mkdir -p "{data_dir}"
+ExecStart={binary}{args}
+Restart=always
+RestartSec=5
+WatchdogSec=60
+
+# Sandboxing
+NoNewPrivileges=yes
+PrivateTmp=yes
+ProtectSystem=strict
+ReadWritePaths={read_write_paths}
+RestrictAddressFamilies={address_families}
+RestrictNamespaces=yes
+RestrictRealtime=yes
+RestrictSUIDSGID=yes
+LockPersonality=yes
+MemoryDenyWriteExecute=yes
+SystemCallArchitectures=native
+SystemCallFilter=@system-service
+SystemCallErrorNumber=EPERM
+
+[Install]
+WantedBy=default.target
+"#,
+        binary = binary_str,
+        read_write_paths = read_write_paths
+    )
+}
+
+/// 生成 macOS LaunchAgent plist 内容
+pub fn generate_plist(binary_path: &Path, log_dir: &Path) -> String {
+    let binary_str = binary_path.to_str().unwrap_or("");
+    let stdout_log = log_dir.join("service.log");
+    let stderr_log = log_dir.join("service.err");
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{binary}</string>
+        <string>daemon</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+    <key>StandardOutPath</key>
+    <string>{stdout}</string>
+    <key>StandardErrorPath</key>
+    <string>{stderr}</string>
+</dict>
+</plist>
+"#,
+        label = APP_LABEL,
+        binary = binary_str,
+        stdout = stdout_log.to_str().unwrap_or(""),
+        stderr = stderr_log.to_str().unwrap_or("")
+    )
+}
+
+/// 生成 Windows 开机自启动注册表值（`"C:\path\to\exe" daemon`）
+pub fn reg_value(binary_path: &Path) -> String {
+    format!("\"{}\" daemon", binary_path.to_str().unwrap_or(""))
+}
+
+/// 生成写入开机自启动注册表项的 `reg` 命令参数
+pub fn reg_add_args(binary_path: &Path) -> Vec<String> {
+    [
+        "add",
+        REG_PATH,
+        "/v",
+        REG_KEY_NAME,
+        "/t",
+        "REG_SZ",
+        "/d",
+        &reg_value(binary_path),
+        "/f",
+    ]
+    .map(str::to_string)
+    .to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_unit_file_sandboxing() {
+        let unit = generate_unit_file(
+            Path::new("/home/u/.local/share/app/edge-copilot-helper"),
+            Path::new("/home/u/.local/share/app"),
+            &[
+                PathBuf::from("/home/u/.config/microsoft-edge"),
+                PathBuf::from("/home/u/.config/microsoft-edge-beta"),
+            ],
+            &MetricsOptions::default(),
+        );
+        assert!(unit.contains("ExecStart=/home/u/.local/share/app/edge-copilot-helper daemon"));
+        assert!(unit.contains("ExecStartPre=+/bin/mkdir -p \"/home/u/.local/share/app\""));
+        assert!(unit.contains("Environment=\"XDG_DATA_HOME=/home/u/.local/share\"\n"));
+        assert!(unit.contains("ProtectSystem=strict"));
+        assert!(unit.contains(
+            "ReadWritePaths=\"/home/u/.local/share/app\" \"-/home/u/.config/microsoft-edge\" \"-/home/u/.config/microsoft-edge-beta\""
+        ));
+        assert!(unit.contains("NoNewPrivileges=yes"));
+        assert!(unit.contains("RestrictAddressFamilies=AF_UNIX\n"));
+    }
+
+    #[test]
+    fn test_generate_unit_file_opens_sandbox_for_metrics() {
+        let unit = generate_unit_file(
+            Path::new("/usr/local/bin/edge-copilot-helper"),
+            Path::new("%h/.local/share/app"),
+            &[PathBuf::from("%h/.config/microsoft-edge")],
+            &MetricsOptions {
+                textfile: Some(PathBuf::from("/var/lib/node_exporter/edge.prom")),
+                listen: Some("127.0.0.1:9477".parse().unwrap()),
+            },
+        );
+        assert!(unit.contains(
+            "ExecStart=/usr/local/bin/edge-copilot-helper daemon --metrics-textfile \"/var/lib/node_exporter/edge.prom\" --metrics-listen 127.0.0.1:9477\n"
+        ));
+        assert!(unit.contains(
+            "ReadWritePaths=\"%h/.local/share/app\" \"-%h/.config/microsoft-edge\" \"-/var/lib/node_exporter\""
+        ));
+        assert!(unit.contains("RestrictAddressFamilies=AF_UNIX AF_INET AF_INET6\n"));
+        assert!(unit.contains("Environment=\"XDG_DATA_HOME=%h/.local/share\"\n"));
+    }
+
+    #[test]
+    fn test_generate_desktop_entry() {
+        let entry = generate_desktop_entry(Path::new("/home/u/my apps/edge-copilot-helper"));
+        assert!(entry.starts_with("[Desktop Entry]\n"));
+        assert!(entry.contains("Exec=\"/home/u/my apps/edge-copilot-helper\" daemon\n"));
+        assert!(entry.contains("Type=Application"));
+    }
+
+    #[test]
+    fn test_generate_plist() {
+        let plist = generate_plist(
+            Path::new("/Users/u/Library/Application Support/app/edge-copilot-helper"),
+            Path::new("/Users/u/Library/Logs/app"),
+        );
+        assert!(plist.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"));
+        assert!(plist.contains(&format!("<string>{APP_LABEL}</string>")));
+        assert!(plist.contains(
+            "<string>/Users/u/Library/Application Support/app/edge-copilot-helper</string>\n        <string>daemon</string>"
+        ));
+        assert!(plist.contains("<string>/Users/u/Library/Logs/app/service.log</string>"));
+        assert!(plist.contains("<string>/Users/u/Library/Logs/app/service.err</string>"));
+    }
+
+    #[test]
+    fn test_reg_add_args() {
+        let args = reg_add_args(Path::new(
+            r"C:\Users\u\AppData\Local\app\edge-copilot-helper.exe",
+        ));
+        assert_eq!(
+            args,
+            [
+                "add",
+                REG_PATH,
+                "/v",
+                REG_KEY_NAME,
+                "/t",
+                "REG_SZ",
+                "/d",
+                r#""C:\Users\u\AppData\Local\app\edge-copilot-helper.exe" daemon"#,
+                "/f",
+            ]
+        );
+    }
+}
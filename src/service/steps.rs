@@ -0,0 +1,208 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::manifest::{FileKind, Manifest};
+
+/// install 各步骤的执行器
+///
+/// 正常模式下执行文件操作和外部命令，并把创建的产物记录到安装清单；
+/// dry-run 模式下只打印将要执行的操作、目标路径和完整的文件内容，不做任何修改。
+pub struct InstallSteps {
+    dry_run: bool,
+    manifest: Manifest,
+    manifest_path: PathBuf,
+}
+
+impl InstallSteps {
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            manifest: Manifest::new(),
+            manifest_path: Manifest::path(),
+        }
+    }
+
+    /// 将安装清单保存到指定位置（系统级安装使用）
+    #[cfg(target_os = "linux")]
+    pub fn with_manifest_path(mut self, path: PathBuf) -> Self {
+        self.manifest_path = path;
+        self
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// 输出步骤描述；dry-run 模式下带有 `[dry-run]` 前缀
+    pub fn step(&self, message: &str) {
+        if self.dry_run {
+            log::info!("[dry-run] {message}");
+        } else {
+            log::info!("{message}");
+        }
+    }
+
+    pub fn create_dir_all(&self, path: &Path) -> Result<()> {
+        if self.dry_run {
+            log::info!("[dry-run]   mkdir -p {}", path.display());
+            return Ok(());
+        }
+        fs::create_dir_all(path)
+            .with_context(|| format!("Failed to create directory: {}", path.display()))
+    }
+
+    /// 复制可执行文件并记录到清单
+    pub fn install_binary(&mut self, from: &Path, to: &Path) -> Result<()> {
+        if self.dry_run {
+            log::info!("[dry-run]   copy {} -> {}", from.display(), to.display());
+            return Ok(());
+        }
+
+        fs::copy(from, to).with_context(|| format!("Failed to copy binary to {}", to.display()))?;
+
+        // Make executable
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut perms = fs::metadata(to)?.permissions();
+            perms.set_mode(0o755);
+            fs::set_permissions(to, perms)?;
+        }
+
+        self.manifest.add_file(FileKind::Binary, to)
+    }
+
+    /// 写入服务配置文件并记录到清单；dry-run 模式下打印完整内容
+    #[cfg_attr(target_os = "windows", allow(dead_code))]
+    pub fn write_file(&mut self, kind: FileKind, path: &Path, content: &str) -> Result<()> {
+        if self.dry_run {
+            log::info!("[dry-run]   write {}:", path.display());
+            println!("{content}");
+            return Ok(());
+        }
+
+        fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+        self.manifest.add_file(kind, path)
+    }
+
+    /// 在清单中记录写入 systemd unit 的指标选项
+    #[cfg(target_os = "linux")]
+    pub fn record_metrics(&mut self, metrics: &crate::metrics::MetricsOptions) {
+        self.manifest.metrics = Some(metrics.clone());
+    }
+
+    /// 删除文件（不存在时忽略）
+    #[cfg_attr(target_os = "macos", allow(dead_code))]
+    pub fn remove_file(&self, path: &Path) -> Result<()> {
+        if !path.exists() {
+            return Ok(());
+        }
+        if self.dry_run {
+            log::info!("[dry-run]   rm {}", path.display());
+            return Ok(());
+        }
+        fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))
+    }
+
+    /// 执行外部命令，返回是否成功；dry-run 模式下只打印命令行并视为成功
+    pub fn run(&self, program: &str, args: &[&str]) -> Result<bool> {
+        if self.dry_run {
+            log::info!("[dry-run]   $ {}", command_line(program, args));
+            return Ok(true);
+        }
+
+        let status = Command::new(program)
+            .args(args)
+            .status()
+            .with_context(|| format!("Failed to execute {}", command_line(program, args)))?;
+        Ok(status.success())
+    }
+
+    /// 执行外部命令并忽略结果（用于停止可能不存在的旧服务等尽力而为的操作）
+    pub fn run_quiet(&self, program: &str, args: &[&str]) {
+        if self.dry_run {
+            log::info!("[dry-run]   $ {}", command_line(program, args));
+            return;
+        }
+        let _ = Command::new(program).args(args).output();
+    }
+
+    /// 执行其他操作（如停止进程）；dry-run 模式下只打印描述
+    #[cfg_attr(target_os = "macos", allow(dead_code))]
+    pub fn action(&self, description: &str, f: impl FnOnce() -> Result<()>) -> Result<()> {
+        if self.dry_run {
+            log::info!("[dry-run]   {description}");
+            return Ok(());
+        }
+        f()
+    }
+
+    /// 记录已写入的注册表值
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    pub fn record_registry(&mut self, key: &str, value: &str, data: &str) {
+        self.manifest.add_registry(key, value, data);
+    }
+
+    /// 保存安装清单；在服务启动前调用，保证启动失败时 uninstall 仍能找到已写入的文件
+    ///
+    /// 覆盖旧清单前先删除旧清单中记录、但本次安装没有重新写入的文件（例如从 systemd
+    /// 改为 `--method=none` 时的 unit），否则它们不再被任何清单记录，uninstall 也找不到。
+    pub fn save_manifest(&self) -> Result<()> {
+        let previous = match Manifest::load_from(&self.manifest_path) {
+            Ok(previous) => previous,
+            Err(e) => {
+                log::warn!("⚠️ Ignoring the previous install manifest: {e:#}");
+                None
+            }
+        };
+
+        if self.dry_run {
+            for entry in previous.iter().flat_map(|p| p.stale_files(&self.manifest)) {
+                log::info!("[dry-run]   rm {}", entry.path.display());
+            }
+            log::info!("[dry-run]   write {}", self.manifest_path.display());
+            return Ok(());
+        }
+        if let Some(previous) = previous {
+            previous.remove_stale_files(&self.manifest)?;
+        }
+        self.manifest.save_to(&self.manifest_path)
+    }
+}
+
+/// 将命令格式化为可复制到终端的形式
+fn command_line(program: &str, args: &[&str]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().copied())
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("\"{arg}\"")
+            } else {
+                arg.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line_quotes_arguments_with_spaces() {
+        assert_eq!(
+            command_line(
+                "reg",
+                &["add", r"HKCU\Run", "/d", r#""C:\My Apps\x.exe" daemon"#]
+            ),
+            r#"reg add HKCU\Run /d ""C:\My Apps\x.exe" daemon""#
+        );
+        assert_eq!(
+            command_line("systemctl", &["--user", "daemon-reload"]),
+            "systemctl --user daemon-reload"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::manifest::{FileKind, Manifest};
+use super::{regenerate_service_files, restore_service_files, start_service, stop_service};
+use crate::constants::paths;
+use crate::lock;
+
+/// 等待新实例拿到单实例锁的最长时间
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 新实例拿到锁后还需持续运行的时间（排除启动即崩溃的情况）
+const STABLE_PERIOD: Duration = Duration::from_secs(3);
+
+/// upgrade 命令的选项
+#[derive(Debug, Clone, Default)]
+pub struct UpgradeOptions {
+    /// 版本相同或已安装版本更新时也强制替换
+    pub force: bool,
+}
+
+/// 就地升级已安装的二进制文件
+///
+/// 1. 比较当前二进制与已安装二进制的版本
+/// 2. 将新二进制复制到旧文件旁边，保留旧文件作为回滚副本，再原子替换
+/// 3. 按当前模板重新生成服务文件，按安装方式重启服务，并通过单实例锁确认新 daemon 已正常运行
+///    （`--method=none` 安装不启动 daemon）
+/// 4. 新 daemon 未能启动时恢复旧二进制并重新启动
+pub fn upgrade(options: &UpgradeOptions) -> Result<()> {
+    log::info!("Upgrading Edge Copilot Helper...");
+
+    let current_exe = std::env::current_exe().context("Failed to get current executable path")?;
+    let binary_path = paths::binary_path();
+
+    if !binary_path.exists() {
+        anyhow::bail!(
+            "Edge Copilot Helper is not installed at {}; run install first",
+            binary_path.display()
+        );
+    }
+
+    // 1. Compare versions
+    let new_version = env!("CARGO_PKG_VERSION");
+    let installed_version = binary_version(&binary_path)?;
+    log::info!("  Installed: {installed_version}");
+    log::info!("  New:       {new_version}");
+
+    match compare_versions(new_version, &installed_version) {
+        std::cmp::Ordering::Equal if !options.force => {
+            log::info!("Already up to date.");
+            return Ok(());
+        }
+        std::cmp::Ordering::Less if !options.force => {
+            anyhow::bail!(
+                "Installed version {installed_version} is newer than {new_version}; use --force to downgrade"
+            );
+        }
+        _ => {}
+    }
+
+    // 2. Stage the new binary next to the old one, then swap
+    let staged_path = sibling_path(&binary_path, "new");
+    let previous_path = sibling_path(&binary_path, "old");
+
+    log::info!("Staging new binary: {}", staged_path.display());
+    fs::copy(&current_exe, &staged_path)
+        .with_context(|| format!("Failed to copy binary to {}", staged_path.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = fs::metadata(&staged_path)?.permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&staged_path, perms)?;
+    }
+
+    log::info!("Switching binaries...");
+    if let Err(e) = swap_binary(&binary_path, &staged_path, &previous_path) {
+        let _ = fs::remove_file(&staged_path);
+        return Err(e);
+    }
+
+    // 3. Restart and verify
+    log::info!("Restarting service...");
+    let previous_pid = lock::running_instance().map(|holder| holder.pid);
+    stop_service();
+    let mut previous_unit = None;
+    let healthy = regenerate_service_files()
+        .and_then(|previous| {
+            previous_unit = previous;
+            start_service()
+        })
+        .and_then(|started| {
+            if started {
+                wait_until_healthy(&binary_path, new_version, previous_pid)
+            } else {
+                Ok(())
+            }
+        });
+
+    if let Err(e) = healthy {
+        // 4. Roll back
+        log::error!("❌ New version failed to start: {e}");
+        log::warn!("Rolling back to {installed_version}...");
+        stop_service();
+        fs::rename(&previous_path, &binary_path).with_context(|| {
+            format!(
+                "Failed to restore previous binary from {}",
+                previous_path.display()
+            )
+        })?;
+        if let Some(previous) = &previous_unit {
+            restore_service_files(previous)?;
+        }
+        start_service()?;
+        anyhow::bail!("Upgrade to {new_version} failed, rolled back to {installed_version}");
+    }
+
+    let _ = fs::remove_file(&previous_path);
+
+    if let Some(mut manifest) = Manifest::load()? {
+        manifest.update_file(FileKind::Binary, &binary_path)?;
+        manifest.version = new_version.to_string();
+        manifest.save()?;
+    }
+
+    log::info!("");
+    log::info!("Upgraded from {installed_version} to {new_version} successfully!");
+
+    Ok(())
+}
+
+/// 原子地用暂存的新二进制替换旧二进制，旧二进制保留为 `previous`
+fn swap_binary(binary: &Path, staged: &Path, previous: &Path) -> Result<()> {
+    let _ = fs::remove_file(previous);
+
+    // Unix 上 rename 会原子替换目标文件，运行中的旧进程不受影响；
+    // Windows 上不能覆盖正在运行的 exe，但可以先把它改名挪开
+    #[cfg(unix)]
+    fs::copy(binary, previous)
+        .with_context(|| format!("Failed to back up binary to {}", previous.display()))?;
+    #[cfg(windows)]
+    fs::rename(binary, previous)
+        .with_context(|| format!("Failed to move binary to {}", previous.display()))?;
+
+    if let Err(e) = fs::rename(staged, binary) {
+        #[cfg(windows)]
+        let _ = fs::rename(previous, binary);
+        return Err(e).with_context(|| format!("Failed to replace {}", binary.display()));
+    }
+
+    Ok(())
+}
+
+/// 等待新 daemon 拿到单实例锁并稳定运行
+///
+/// 只读取锁文件中的持有者信息，不获取锁，避免新 daemon 启动时因锁被占用而退出。
+/// 持有者必须是新版本，且不是升级前的实例 `previous_pid`。
+fn wait_until_healthy(
+    binary: &Path,
+    expected_version: &str,
+    previous_pid: Option<u32>,
+) -> Result<()> {
+    let installed = binary_version(binary)?;
+    if installed != expected_version {
+        anyhow::bail!("Installed binary reports version {installed}, expected {expected_version}");
+    }
+
+    let is_new = |holder: &lock::LockInfo| {
+        holder.version == expected_version && Some(holder.pid) != previous_pid
+    };
+
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    let daemon = loop {
+        if let Some(holder) = lock::running_instance().filter(is_new) {
+            break holder;
+        }
+        if Instant::now() >= deadline {
+            anyhow::bail!(
+                "Daemon {expected_version} did not start within {} seconds",
+                STARTUP_TIMEOUT.as_secs()
+            );
+        }
+        thread::sleep(Duration::from_millis(500));
+    };
+
+    thread::sleep(STABLE_PERIOD);
+    if lock::running_instance().is_none_or(|holder| holder.pid != daemon.pid) {
+        anyhow::bail!("Daemon exited shortly after starting");
+    }
+
+    log::info!("New daemon is running ({daemon}).");
+    Ok(())
+}
+
+/// 通过 `--version` 读取二进制文件的版本号
+fn binary_version(binary: &Path) -> Result<String> {
+    let output = Command::new(binary)
+        .arg("--version")
+        .output()
+        .with_context(|| format!("Failed to run {} --version", binary.display()))?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .last()
+        .map(str::to_string)
+        .with_context(|| format!("Could not determine version of {}", binary.display()))
+}
+
+/// 按数字逐段比较版本号（如 `0.1.14` 与 `0.1.9`）
+fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    let parse = |v: &str| -> Vec<u64> {
+        v.split(['.', '-'])
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    parse(a).cmp(&parse(b))
+}
+
+/// 在二进制文件旁生成带后缀的路径，如 `edge-copilot-helper.new`
+fn sibling_path(binary: &Path, suffix: &str) -> PathBuf {
+    let mut name = binary.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    binary.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("0.1.14", "0.1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("0.1.14", "0.1.14"), Ordering::Equal);
+        assert_eq!(compare_versions("0.1.14", "0.2.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.0", "0.99.99"), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_sibling_path() {
+        assert_eq!(
+            sibling_path(Path::new("/opt/app/edge-copilot-helper"), "new"),
+            PathBuf::from("/opt/app/edge-copilot-helper.new")
+        );
+        assert_eq!(
+            sibling_path(Path::new("C:/app/edge-copilot-helper.exe"), "old"),
+            PathBuf::from("C:/app/edge-copilot-helper.exe.old")
+        );
+    }
+}
@@ -1,70 +1,63 @@
 use anyhow::{Context, Result};
-use std::fs;
 use std::process::Command;
 
+use super::manifest::{FileKind, Manifest};
+use super::render::{REG_KEY_NAME, REG_PATH, reg_add_args, reg_value};
+use super::steps::InstallSteps;
+use super::{InstallOptions, UninstallOptions, remove_data, stop_running_instance};
 use crate::constants::paths;
-use sysinfo::{Pid, System};
-
-const REG_KEY_NAME: &str = "EdgeCopilotHelper";
-const REG_PATH: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
-
-pub fn install() -> Result<()> {
-    log::info!("Installing Edge Copilot Helper...");
+use crate::error::Error;
 
+pub fn install(options: &InstallOptions) -> Result<()> {
     let current_exe = std::env::current_exe().context("Failed to get current executable path")?;
     let install_dir = paths::install_dir();
     let log_dir = paths::log_dir();
     let binary_path = paths::binary_path();
 
+    let reg_args = reg_add_args(&binary_path);
+    let reg_args: Vec<&str> = reg_args.iter().map(String::as_str).collect();
+    if options.print_unit {
+        println!("reg {}", reg_args.join(" "));
+        return Ok(());
+    }
+
+    let mut steps = InstallSteps::new(options.dry_run);
+    steps.step("Installing Edge Copilot Helper...");
+
     // 0. Stop existing running instance to avoid copy failures
-    log::info!("Stopping running instances (if any)...");
-    stop_running_instances();
+    steps.step("Stopping running instance (if any)...");
+    steps.action("stop the running edge-copilot-helper.exe instance", || {
+        stop_running_instance();
+        Ok(())
+    })?;
 
     // 1. Create directories
-    log::info!("Creating directories...");
-    fs::create_dir_all(&install_dir).with_context(|| {
-        format!(
-            "Failed to create install directory: {}",
-            install_dir.display()
-        )
-    })?;
-    fs::create_dir_all(&log_dir)
-        .with_context(|| format!("Failed to create log directory: {}", log_dir.display()))?;
+    steps.step("Creating directories...");
+    steps.create_dir_all(&install_dir)?;
+    steps.create_dir_all(&log_dir)?;
 
     // Remove old binary if present (best-effort overwrite)
     if binary_path.exists() {
-        log::info!("Removing existing binary...");
-        let _ = fs::remove_file(&binary_path);
+        steps.step("Removing existing binary...");
+        let _ = steps.remove_file(&binary_path);
     }
 
     // 2. Copy binary
-    log::info!("Installing binary...");
-    fs::copy(&current_exe, &binary_path)
-        .with_context(|| format!("Failed to copy binary to {}", binary_path.display()))?;
+    steps.step("Installing binary...");
+    steps.install_binary(&current_exe, &binary_path)?;
 
     // 3. Add to startup registry (HKCU\Run)
-    log::info!("Adding to startup registry...");
-    let bin_path = binary_path.to_str().unwrap_or("");
-    // Format: "C:\path\to\exe" daemon
-    let reg_value = format!("\"{}\" daemon", bin_path);
-
-    let status = Command::new("reg")
-        .args([
-            "add",
-            REG_PATH,
-            "/v",
-            REG_KEY_NAME,
-            "/t",
-            "REG_SZ",
-            "/d",
-            &reg_value,
-            "/f",
-        ])
-        .status()
-        .context("Failed to execute reg add")?;
-
-    if !status.success() {
-        anyhow::bail!("Failed to add registry entry for startup");
+    steps.step("Adding to startup registry...");
+    if !steps.run("reg", &reg_args)? {
+        anyhow::bail!(Error::ServiceManager(
+            "Failed to add registry entry for startup".into()
+        ));
+    }
+    steps.record_registry(REG_PATH, REG_KEY_NAME, &reg_value(&binary_path));
+    steps.save_manifest()?;
+
+    if steps.is_dry_run() {
+        return Ok(());
     }
 
     log::info!("");
@@ -78,36 +71,46 @@ pub fn install() -> Result<()> {
     Ok(())
 }
 
-pub fn uninstall() -> Result<()> {
+pub fn uninstall(options: &UninstallOptions) -> Result<()> {
     log::info!("Uninstalling Edge Copilot Helper...");
 
-    let install_dir = paths::install_dir();
+    let manifest = match Manifest::load()? {
+        Some(manifest) => manifest,
+        None => {
+            log::warn!("⚠️ No install manifest found, removing known install locations.");
+            let mut manifest = Manifest::new();
+            manifest.add_legacy_file(FileKind::Binary, paths::binary_path());
+            manifest.add_registry(REG_PATH, REG_KEY_NAME, "");
+            manifest
+        }
+    };
 
     // Stop running instance
-    log::info!("Stopping running instances (if any)...");
-    stop_running_instances();
+    log::info!("Stopping running instance (if any)...");
+    stop_running_instance();
 
     // 1. Remove from startup registry
     log::info!("Removing from startup registry...");
-    let status = Command::new("reg")
-        .args(["delete", REG_PATH, "/v", REG_KEY_NAME, "/f"])
-        .status();
-
-    match status {
-        Ok(s) if s.success() => {
-            log::info!("Removed from startup registry.");
-        }
-        _ => {
-            log::warn!("Warning: Failed to remove registry entry. It may not exist.");
+    for entry in &manifest.registry {
+        let status = Command::new("reg")
+            .args(["delete", &entry.key, "/v", &entry.value, "/f"])
+            .status();
+
+        match status {
+            Ok(s) if s.success() => {
+                log::info!("Removed from startup registry.");
+            }
+            _ => {
+                log::warn!("Warning: Failed to remove registry entry. It may not exist.");
+            }
         }
     }
 
-    // 2. Remove install directory (includes binary and logs)
-    if install_dir.exists() {
-        log::info!("Removing files: {}", install_dir.display());
-        fs::remove_dir_all(&install_dir)
-            .with_context(|| format!("Failed to remove {}", install_dir.display()))?;
-    }
+    // 2. Remove installed files (binary)
+    manifest.remove_files()?;
+
+    // 3. Remove logs, backups and runtime files
+    remove_data(options)?;
 
     log::info!("");
     log::info!("Uninstallation complete.");
@@ -115,17 +118,16 @@ pub fn uninstall() -> Result<()> {
     Ok(())
 }
 
-fn stop_running_instances() {
-    let mut sys = System::new();
-    sys.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
-    let current_pid = Pid::from_u32(std::process::id());
-
-    for (pid, process) in sys.processes() {
-        let name = process.name().to_string_lossy();
-        if name.eq_ignore_ascii_case("edge-copilot-helper.exe") && *pid != current_pid {
-            log::info!("Stopping existing instance (pid {}): {}", pid, name);
-            // Try graceful kill first
-            let _ = process.kill();
-        }
-    }
+/// 停止已安装的服务（upgrade 使用）
+pub fn stop_service() {
+    stop_running_instance();
+}
+
+/// 启动已安装的服务（upgrade 使用）
+pub fn start_service() -> Result<()> {
+    Command::new(paths::binary_path())
+        .arg("daemon")
+        .spawn()
+        .context("Failed to start daemon")?;
+    Ok(())
 }
@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 是否已请求退出
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// 退出原因（首次请求时记录）
+static REASON: OnceLock<&'static str> = OnceLock::new();
+
+/// 修复进行期间持有，退出前等待其释放，避免写到一半的配置文件
+static PATCHING: Mutex<()> = Mutex::new(());
+
+/// 安装 SIGTERM / SIGINT / SIGHUP（Windows 上为 Ctrl+C / Ctrl+Break / 关闭控制台）处理器
+///
+/// 收到信号后只设置退出标志，由服务循环在完成当前修复后自行退出。
+/// macOS 的 `NSRunLoop` 无法从其他线程打断，因此在等待当前修复完成后直接结束进程。
+pub(crate) fn install_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        request("received termination signal");
+
+        #[cfg(target_os = "macos")]
+        {
+            let _patching = patching();
+            log::info!("👋 Shutting down: received termination signal");
+            log::logger().flush();
+            std::process::exit(0);
+        }
+    })
+    .context("Failed to install signal handler")
+}
+
+/// 请求服务循环退出
+pub(crate) fn request(reason: &'static str) {
+    let _ = REASON.set(reason);
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// 是否已请求退出
+pub(crate) fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// 退出原因
+pub(crate) fn reason() -> &'static str {
+    REASON.get().copied().unwrap_or("unknown")
+}
+
+/// 标记一次修复正在进行，返回的守卫被 drop 前信号处理器不会结束进程
+pub(crate) fn patching() -> MutexGuard<'static, ()> {
+    PATCHING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 休眠指定时长，期间收到退出请求时提前返回
+///
+/// Windows 上没有 `SIGTERM`，其他实例通过停止请求文件要求本进程退出，也在这里检查。
+pub(crate) fn sleep(duration: Duration) {
+    let deadline = Instant::now() + duration;
+    while !requested() {
+        #[cfg(target_os = "windows")]
+        if crate::lock::take_stop_request() {
+            request("another instance asked this one to stop");
+            return;
+        }
+        let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+            return;
+        };
+        thread::sleep(remaining.min(Duration::from_millis(100)));
+    }
+}
@@ -0,0 +1,377 @@
+use anyhow::{Context, Result};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::constants::{BACKUPS_DIR, STATE_FILE, paths};
+
+/// 被修改的键在首次修改前的状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum OriginalValue {
+    /// 键原本不存在（由 helper 添加）
+    Missing,
+    /// 键原本的值
+    Present(Value),
+}
+
+impl OriginalValue {
+    pub fn from_lookup(value: Option<&Value>) -> Self {
+        match value {
+            Some(value) => Self::Present(value.clone()),
+            None => Self::Missing,
+        }
+    }
+}
+
+/// 单个配置文件的状态与修改历史
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileState {
+    /// 按键路径记录的原始值（首次修改前），revert 后清空
+    #[serde(default)]
+    pub original: BTreeMap<String, OriginalValue>,
+    /// 最近一次修改前的值
+    #[serde(default)]
+    pub previous: BTreeMap<String, OriginalValue>,
+    /// 当前是否处于已修复状态；此时再次需要修改说明 Edge 把值改回去了
+    #[serde(default)]
+    pub applied: bool,
+    /// 最近一次成功修改的时间（RFC 3339）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_patched: Option<String>,
+    /// 最近一次修改时 `Local State` 记录的 Edge 版本
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edge_version: Option<String>,
+    /// 成功修改的次数
+    #[serde(default)]
+    pub patch_count: u64,
+    /// 修改后又被 Edge 改回的次数
+    #[serde(default)]
+    pub reset_count: u64,
+    /// 处理失败的次数
+    #[serde(default)]
+    pub failure_count: u64,
+    /// 最近一次失败的时间（RFC 3339）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_failure: Option<String>,
+    /// 最近一次失败的错误信息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl FileState {
+    /// 三方合并：把本进程相对 `base` 的修改 `ours` 应用到磁盘上的最新内容 `disk`
+    ///
+    /// 原始值按键合并（同一个键已被其他进程记录时保留最早的值），计数器累加增量，
+    /// 其余字段只在本进程修改过时覆盖。
+    fn merge(base: Option<&FileState>, ours: &FileState, disk: Option<FileState>) -> FileState {
+        let Some(mut merged) = disk else {
+            return ours.clone();
+        };
+        let empty = FileState::default();
+        let base = base.unwrap_or(&empty);
+
+        for key in base.original.keys() {
+            if !ours.original.contains_key(key) {
+                merged.original.remove(key);
+            }
+        }
+        for (key, value) in &ours.original {
+            if !base.original.contains_key(key) {
+                merged
+                    .original
+                    .entry(key.clone())
+                    .or_insert_with(|| value.clone());
+            }
+        }
+
+        merged.patch_count += ours.patch_count.saturating_sub(base.patch_count);
+        merged.reset_count += ours.reset_count.saturating_sub(base.reset_count);
+        merged.failure_count += ours.failure_count.saturating_sub(base.failure_count);
+
+        if ours.previous != base.previous {
+            merged.previous = ours.previous.clone();
+        }
+        if ours.applied != base.applied {
+            merged.applied = ours.applied;
+        }
+        if ours.last_patched != base.last_patched {
+            merged.last_patched = ours.last_patched.clone();
+        }
+        if ours.edge_version != base.edge_version {
+            merged.edge_version = ours.edge_version.clone();
+        }
+        if ours.last_failure != base.last_failure {
+            merged.last_failure = ours.last_failure.clone();
+            merged.last_error = ours.last_error.clone();
+        }
+        merged
+    }
+}
+
+/// 状态数据库：记录每个被修改的键在首次修改前的值，供 `revert` 恢复
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct State {
+    /// 按配置文件绝对路径索引
+    #[serde(default)]
+    pub files: BTreeMap<PathBuf, FileState>,
+    /// 数据库文件路径
+    #[serde(skip)]
+    path: PathBuf,
+    /// 上次读取或保存时磁盘上的内容，保存时据此找出本进程的修改
+    #[serde(skip)]
+    base: BTreeMap<PathBuf, FileState>,
+}
+
+impl State {
+    /// 默认的状态数据库路径（安装目录下的 `state.json`）
+    pub fn default_path() -> PathBuf {
+        paths::install_dir().join(STATE_FILE)
+    }
+
+    /// 读取状态数据库，不存在时返回空状态
+    pub fn load(path: &Path) -> Result<Self> {
+        let files = Self::read_files(path)?;
+        Ok(Self {
+            base: files.clone(),
+            files,
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn read_files(path: &Path) -> Result<BTreeMap<PathBuf, FileState>> {
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let state: Self = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse state database at {}", path.display()))?;
+        Ok(state.files)
+    }
+
+    /// 保存状态数据库；合并后为空时删除文件
+    ///
+    /// `run` 与一次性的 `apply` / `revert` 可能同时更新数据库：在 `<数据库>.lock` 的排他锁内
+    /// 重新读取磁盘上的内容，只合并本进程自上次读取或保存以来的修改（见 [`FileState::merge`]），
+    /// 再写入以 PID 命名的临时文件并重命名。保存后本对象与磁盘内容一致。
+    pub fn save(&mut self) -> Result<()> {
+        let path = self.path.clone();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+
+        let lock_path = sibling(&path, ".lock");
+        let lock = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open {}", lock_path.display()))?;
+        lock.lock_exclusive()
+            .with_context(|| format!("Failed to lock {}", lock_path.display()))?;
+
+        let mut merged = Self::read_files(&path)?;
+        for file in self.base.keys() {
+            if !self.files.contains_key(file) {
+                merged.remove(file);
+            }
+        }
+        for (file, ours) in &self.files {
+            let base = self.base.get(file);
+            if base != Some(ours) {
+                let disk = merged.remove(file);
+                merged.insert(file.clone(), FileState::merge(base, ours, disk));
+            }
+        }
+        self.files = merged;
+        self.base = self.files.clone();
+
+        if self.files.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+            return Ok(());
+        }
+
+        let tmp_path = sibling(&path, &format!(".{}.tmp", std::process::id()));
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .with_context(|| format!("Failed to write state database to {}", path.display()))
+    }
+
+    /// 数据库文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 配置文件备份目录（与数据库位于同一目录）
+    pub fn backup_dir(&self) -> PathBuf {
+        self.path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(BACKUPS_DIR)
+    }
+
+    /// 记录一次成功的修改，返回该文件修改后又被 Edge 改回的累计次数（本次不是重新修复时为 `None`）
+    pub fn record_patch(
+        &mut self,
+        file: &Path,
+        previous: BTreeMap<String, OriginalValue>,
+        edge_version: Option<String>,
+    ) -> Option<u64> {
+        let entry = self.files.entry(file.to_path_buf()).or_default();
+        let was_reset = entry.applied;
+        if was_reset {
+            entry.reset_count += 1;
+        }
+        entry.applied = true;
+        entry.previous = previous;
+        entry.last_patched = Some(chrono::Local::now().to_rfc3339());
+        entry.edge_version = edge_version.or(entry.edge_version.take());
+        entry.patch_count += 1;
+        was_reset.then_some(entry.reset_count)
+    }
+
+    /// 记录一次失败
+    pub fn record_failure(&mut self, file: &Path, error: &str) {
+        let entry = self.files.entry(file.to_path_buf()).or_default();
+        entry.failure_count += 1;
+        entry.last_failure = Some(chrono::Local::now().to_rfc3339());
+        entry.last_error = Some(error.to_string());
+    }
+
+    /// 记录键的原始值；已记录过的键保留最早的值，返回是否新增了记录
+    pub fn record_original(&mut self, file: &Path, key_path: &str, value: OriginalValue) -> bool {
+        let original = &mut self.files.entry(file.to_path_buf()).or_default().original;
+        if original.contains_key(key_path) {
+            return false;
+        }
+        original.insert(key_path.to_string(), value);
+        true
+    }
+}
+
+/// 同目录下在文件名后追加后缀的路径
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_record_original_keeps_first_value() {
+        let file = Path::new("/home/u/.config/microsoft-edge/Local State");
+        let mut state = State::default();
+
+        assert!(state.record_original(
+            file,
+            "variations_country",
+            OriginalValue::Present(json!("CN"))
+        ));
+        assert!(!state.record_original(
+            file,
+            "variations_country",
+            OriginalValue::Present(json!("US"))
+        ));
+
+        let json = serde_json::to_string(&state).unwrap();
+        let parsed: State = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            parsed.files[file].original["variations_country"],
+            OriginalValue::Present(json!("CN"))
+        );
+    }
+
+    #[test]
+    fn test_record_patch_counts_resets_by_edge() {
+        let file = Path::new("/home/u/.config/microsoft-edge/Default/Preferences");
+        let mut state = State::default();
+
+        assert_eq!(state.record_patch(file, BTreeMap::new(), None), None);
+        assert_eq!(
+            state.record_patch(file, BTreeMap::new(), Some("130.0.2849.80".to_string())),
+            Some(1)
+        );
+        state.record_failure(file, "Failed to parse JSON");
+
+        let entry = &state.files[file];
+        assert_eq!(entry.patch_count, 2);
+        assert_eq!(entry.reset_count, 1);
+        assert_eq!(entry.failure_count, 1);
+        assert_eq!(entry.edge_version.as_deref(), Some("130.0.2849.80"));
+        assert!(entry.last_patched.is_some());
+    }
+
+    #[test]
+    fn test_concurrent_writers_merge_instead_of_overwriting() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let local_state = Path::new("/home/u/.config/microsoft-edge/Local State");
+        let prefs = Path::new("/home/u/.config/microsoft-edge/Default/Preferences");
+
+        // 例如 `run` 与一次性的 `apply` 同时读取了数据库
+        let mut daemon = State::load(&path).unwrap();
+        let mut apply = State::load(&path).unwrap();
+
+        daemon.record_original(local_state, "variations_country", OriginalValue::Missing);
+        daemon.record_patch(local_state, BTreeMap::new(), None);
+        daemon.save().unwrap();
+
+        apply.record_original(
+            local_state,
+            "variations_country",
+            OriginalValue::Present(json!("US")),
+        );
+        apply.record_patch(local_state, BTreeMap::new(), None);
+        apply.record_original(
+            prefs,
+            "browser.chat_ip_eligibility_status",
+            OriginalValue::Missing,
+        );
+        apply.save().unwrap();
+
+        let state = State::load(&path).unwrap();
+        // 先记录的原始值保留，计数累加，另一个文件的记录也在
+        assert_eq!(
+            state.files[local_state].original["variations_country"],
+            OriginalValue::Missing
+        );
+        assert_eq!(state.files[local_state].patch_count, 2);
+        assert!(state.files.contains_key(prefs));
+
+        // revert 清除原始值不会被另一个进程的保存恢复
+        let mut revert = State::load(&path).unwrap();
+        daemon.record_failure(prefs, "boom");
+        for file in revert.files.values_mut() {
+            file.original.clear();
+        }
+        revert.save().unwrap();
+        daemon.save().unwrap();
+
+        let state = State::load(&path).unwrap();
+        assert!(state.files.values().all(|file| file.original.is_empty()));
+        assert_eq!(state.files[prefs].failure_count, 1);
+    }
+
+    #[test]
+    fn test_original_value_distinguishes_missing_from_null() {
+        let missing = serde_json::to_string(&OriginalValue::Missing).unwrap();
+        let null = serde_json::to_string(&OriginalValue::Present(Value::Null)).unwrap();
+        assert_ne!(missing, null);
+        assert_eq!(
+            serde_json::from_str::<OriginalValue>(&null).unwrap(),
+            OriginalValue::Present(Value::Null)
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use std::fmt::Write;
+
+use crate::lock::is_instance_running;
+use crate::state::{FileState, OriginalValue, State};
+
+/// 输出 helper 运行状态与各配置文件的修改历史
+pub fn status() -> Result<()> {
+    let state = State::load(&State::default_path())?;
+    print!("{}", render(&state, is_instance_running()));
+    Ok(())
+}
+
+/// 生成 `status` 命令的文本输出
+fn render(state: &State, running: bool) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "Helper: {}",
+        if running { "running" } else { "not running" }
+    );
+    let _ = writeln!(out, "State database: {}", state.path().display());
+
+    if state.files.is_empty() {
+        let _ = writeln!(out, "\nNo files have been patched yet.");
+        return out;
+    }
+
+    for (path, file) in &state.files {
+        let _ = writeln!(out, "\n{}", path.display());
+        render_file(&mut out, file);
+    }
+    out
+}
+
+fn render_file(out: &mut String, file: &FileState) {
+    let unknown = || "unknown".to_string();
+    let _ = writeln!(
+        out,
+        "  Edge version:     {}",
+        file.edge_version.clone().unwrap_or_else(unknown)
+    );
+    let _ = writeln!(
+        out,
+        "  Last patched:     {}",
+        file.last_patched
+            .clone()
+            .unwrap_or_else(|| "never".to_string())
+    );
+    let _ = writeln!(out, "  Patched:          {} time(s)", file.patch_count);
+    let _ = writeln!(out, "  Reset by Edge:    {} time(s)", file.reset_count);
+    if !file.applied && file.patch_count > 0 {
+        let _ = writeln!(out, "  Reverted:         yes");
+    }
+    for (key, value) in &file.previous {
+        let _ = writeln!(out, "  Previous {key}: {}", describe(value));
+    }
+    if file.failure_count > 0 {
+        let _ = writeln!(
+            out,
+            "  Failures:         {} (last at {}: {})",
+            file.failure_count,
+            file.last_failure.clone().unwrap_or_else(unknown),
+            file.last_error.clone().unwrap_or_else(unknown)
+        );
+    }
+}
+
+fn describe(value: &OriginalValue) -> String {
+    match value {
+        OriginalValue::Missing => "(missing)".to_string(),
+        OriginalValue::Present(value) => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    #[test]
+    fn test_render_reports_resets_and_failures() {
+        let prefs = Path::new("/home/u/.config/microsoft-edge/Default/Preferences");
+        let mut state = State::default();
+        let previous = BTreeMap::from([(
+            "browser.chat_ip_eligibility_status".to_string(),
+            OriginalValue::Present(json!(false)),
+        )]);
+        state.record_patch(prefs, previous.clone(), Some("130.0.2849.80".to_string()));
+        state.record_patch(prefs, previous, None);
+        state.record_failure(prefs, "Failed to parse JSON");
+
+        let out = render(&state, true);
+        assert!(out.contains("Helper: running"));
+        assert!(out.contains("Edge version:     130.0.2849.80"));
+        assert!(out.contains("Reset by Edge:    1 time(s)"));
+        assert!(out.contains("Previous browser.chat_ip_eligibility_status: false"));
+        assert!(out.contains("Failures:         1"));
+        assert!(out.contains("Failed to parse JSON"));
+    }
+}
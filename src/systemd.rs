@@ -0,0 +1,64 @@
+#![cfg(target_os = "linux")]
+
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+/// 向 systemd 发送 sd_notify 消息
+///
+/// 仅在由 systemd 以 `Type=notify` 启动时（存在 `NOTIFY_SOCKET`）生效，
+/// 其他情况下静默忽略，因此 `run` 命令前台运行时不受影响。
+fn notify(state: &str) {
+    let Some(socket_path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let socket_path = socket_path.to_string_lossy();
+
+    // 以 @ 开头的是抽象命名空间套接字
+    let addr = match socket_path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+        None => SocketAddr::from_pathname(socket_path.as_ref()),
+    };
+
+    let result = addr.and_then(|addr| {
+        let socket = UnixDatagram::unbound()?;
+        socket.send_to_addr(state.as_bytes(), &addr)
+    });
+
+    if let Err(e) = result {
+        log::debug!("sd_notify failed: {e}");
+    }
+}
+
+/// 通知 systemd 服务已就绪
+pub fn ready() {
+    notify("READY=1");
+}
+
+/// 两次喂看门狗之间的最长间隔：systemd 传入的 `WATCHDOG_USEC`（即 unit 文件中 `WatchdogSec=`）的一半
+///
+/// 未启用看门狗，或 `WATCHDOG_PID` 指向其他进程时返回 `None`。
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID")
+        && pid.trim().parse() != Ok(std::process::id())
+    {
+        return None;
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.trim().parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec) / 2)
+}
+
+/// 喂看门狗（需在 `WatchdogSec` 内周期性调用）
+pub fn watchdog() {
+    notify("WATCHDOG=1");
+}
+
+/// 更新 `systemctl status` 中显示的状态描述
+pub fn status(message: &str) {
+    notify(&format!("STATUS={message}"));
+}
+
+/// 通知 systemd 服务正在停止
+pub fn stopping() {
+    notify("STOPPING=1");
+}
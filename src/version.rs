@@ -0,0 +1,317 @@
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::keypath;
+
+/// 经过验证的最新 Edge 主版本；更新的版本可能已经调整了 Copilot 的门控键
+pub const LATEST_TESTED_MAJOR: u32 = 142;
+
+/// 等待 `microsoft-edge --version` 退出的最长时间
+#[cfg(target_os = "linux")]
+const VERSION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Edge 版本号（如 `130.0.2849.80`），按各段数值比较
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EdgeVersion(Vec<u32>);
+
+impl EdgeVersion {
+    /// 解析以 `.` 分隔的数字版本号，忽略 `-64` 等架构后缀
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.trim();
+        let version = version.split('-').next().unwrap_or(version);
+        let parts = version
+            .split('.')
+            .map(|part| part.parse().ok())
+            .collect::<Option<Vec<u32>>>()?;
+        Some(Self(parts))
+    }
+
+    /// 主版本号
+    pub fn major(&self) -> u32 {
+        self.0[0]
+    }
+}
+
+impl fmt::Display for EdgeVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self.0.iter().map(u32::to_string).collect();
+        f.write_str(&parts.join("."))
+    }
+}
+
+/// 修复规则适用的 Edge 主版本范围（两端均包含，`None` 表示不限）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionRange {
+    pub min_major: Option<u32>,
+    pub max_major: Option<u32>,
+}
+
+impl VersionRange {
+    /// 适用于所有版本
+    pub const ALL: Self = Self {
+        min_major: None,
+        max_major: None,
+    };
+
+    /// 版本是否在范围内；无法确定版本时视为适用
+    pub fn contains(&self, version: Option<&EdgeVersion>) -> bool {
+        let Some(version) = version else {
+            return true;
+        };
+        let major = version.major();
+        self.min_major.is_none_or(|min| major >= min)
+            && self.max_major.is_none_or(|max| major <= max)
+    }
+}
+
+impl fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.min_major, self.max_major) {
+            (None, None) => write!(f, "all versions"),
+            (Some(min), None) => write!(f, ">= {min}"),
+            (None, Some(max)) => write!(f, "<= {max}"),
+            (Some(min), Some(max)) => write!(f, "{min}-{max}"),
+        }
+    }
+}
+
+/// 检测用户数据目录对应的 Edge 版本
+///
+/// 读取 `Local State` 中记录的版本；Linux 上对于当前用户自己主目录下的用户数据目录，
+/// 还会参考本机对应渠道的 `microsoft-edge* --version`，两者都可用时取较新的一个
+/// （已升级但尚未重启的 Edge 下次启动时会以新版本读取配置）。
+/// 通过 `--home` 修复其他主目录（如挂载的备份）时，本机安装的 Edge 与其无关，只使用 `Local State`。
+pub fn detect(user_data_dir: &Path) -> Option<EdgeVersion> {
+    let from_local_state = from_user_data_dir(user_data_dir).and_then(|v| EdgeVersion::parse(&v));
+    let in_own_home = dirs::home_dir().is_some_and(|home| user_data_dir.starts_with(home));
+    let from_binary = if in_own_home {
+        from_binary(user_data_dir)
+    } else {
+        None
+    };
+    from_local_state.max(from_binary)
+}
+
+/// 用户数据目录对应渠道的 Edge 可执行文件报告的版本（如 `.config/microsoft-edge-beta` → `microsoft-edge-beta`）
+///
+/// 结果按 `PATH` 中解析出的真实路径和修改时间缓存，避免每次修复都启动 Edge；
+/// Edge 升级替换了可执行文件后（即使浏览器尚未重启）会重新查询。
+#[cfg(target_os = "linux")]
+fn from_binary(user_data_dir: &Path) -> Option<EdgeVersion> {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+    use std::time::SystemTime;
+
+    static CACHE: Mutex<BTreeMap<PathBuf, (SystemTime, Option<EdgeVersion>)>> =
+        Mutex::new(BTreeMap::new());
+
+    let name = user_data_dir
+        .file_name()?
+        .to_str()
+        .filter(|name| name.starts_with("microsoft-edge"))?;
+    let binary = find_in_path(name)?;
+    let modified = fs::metadata(&binary).and_then(|m| m.modified()).ok()?;
+
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached_modified, version)) = cache.get(&binary)
+        && *cached_modified == modified
+    {
+        return version.clone();
+    }
+    let version = run_version(&binary, VERSION_TIMEOUT);
+    cache.insert(binary, (modified, version.clone()));
+    version
+}
+
+/// 在 `PATH` 中查找可执行文件，返回解析符号链接后的真实路径
+#[cfg(target_os = "linux")]
+fn find_in_path(name: &str) -> Option<std::path::PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .filter_map(|dir| fs::canonicalize(dir.join(name)).ok())
+        .find(|path| {
+            fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        })
+}
+
+/// 执行 `<binary> --version` 并解析输出；超过 `timeout` 仍未退出时结束该进程并返回 `None`
+#[cfg(target_os = "linux")]
+fn run_version(binary: &Path, timeout: std::time::Duration) -> Option<EdgeVersion> {
+    use std::io::Read;
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+
+    let mut child = Command::new(binary)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            result => {
+                if result.is_ok() {
+                    log::warn!(
+                        "⚠️ {} --version did not exit within {timeout:?}; ignoring it",
+                        binary.display()
+                    );
+                }
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    };
+    if !status.success() {
+        return None;
+    }
+    let mut stdout = String::new();
+    child.stdout.take()?.read_to_string(&mut stdout).ok()?;
+    parse_version_output(&stdout)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn from_binary(_user_data_dir: &Path) -> Option<EdgeVersion> {
+    None
+}
+
+/// 解析 `Microsoft Edge 130.0.2849.80 beta` 形式的 `--version` 输出
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_version_output(output: &str) -> Option<EdgeVersion> {
+    output
+        .trim()
+        .strip_prefix("Microsoft Edge")?
+        .split_whitespace()
+        .next()
+        .and_then(EdgeVersion::parse)
+}
+
+/// 版本比验证过的最新版本还新时发出警告（每个版本只警告一次）
+pub fn warn_if_untested(version: &EdgeVersion, user_data_dir: &Path) {
+    static WARNED: Mutex<BTreeSet<EdgeVersion>> = Mutex::new(BTreeSet::new());
+
+    if version.major() <= LATEST_TESTED_MAJOR {
+        return;
+    }
+    if let Ok(mut warned) = WARNED.lock()
+        && warned.insert(version.clone())
+    {
+        log::warn!(
+            "⚠️ Edge {version} in {} is newer than the latest tested version ({LATEST_TESTED_MAJOR}); the Copilot gating keys may have moved and the helper may need updating",
+            user_data_dir.display()
+        );
+    }
+}
+
+/// 从 `Local State` 中读取写入该文件的 Edge 版本
+///
+/// 优先使用 `user_experience_metrics.stability.stats_version`（如 `130.0.2849.80-64`，去掉架构后缀），
+/// 其次使用 `variations_permanent_consistency_country` 中记录的版本。
+pub fn from_local_state(json: &Value) -> Option<String> {
+    let stats_version = keypath::get(json, "user_experience_metrics.stability.stats_version")
+        .and_then(Value::as_str)
+        .map(|v| v.split('-').next().unwrap_or(v));
+    let consistency_version = keypath::get(json, "variations_permanent_consistency_country")
+        .and_then(|v| v.get(0))
+        .and_then(Value::as_str);
+
+    stats_version
+        .or(consistency_version)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+/// 读取用户数据目录下 `Local State` 记录的 Edge 版本
+pub fn from_user_data_dir(user_data_dir: &Path) -> Option<String> {
+    let content = fs::read_to_string(user_data_dir.join("Local State")).ok()?;
+    from_local_state(&serde_json::from_str(&content).ok()?)
+}
+
+/// 配置文件所属的用户数据目录：`Local State` 位于其中，`Preferences` 等位于其下的 Profile 目录
+pub fn user_data_dir_of(config_file: &Path) -> Option<&Path> {
+    let parent = config_file.parent()?;
+    if config_file
+        .file_name()
+        .is_some_and(|name| name == "Local State")
+    {
+        Some(parent)
+    } else {
+        parent.parent()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_run_version_kills_hung_binary() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, Instant};
+
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("microsoft-edge");
+        fs::write(&binary, "#!/bin/sh\necho 'Microsoft Edge 130.0.2849.80'\n").unwrap();
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(
+            run_version(&binary, Duration::from_secs(5)),
+            EdgeVersion::parse("130.0.2849.80")
+        );
+
+        fs::write(&binary, "#!/bin/sh\nexec sleep 30\n").unwrap();
+        let started = Instant::now();
+        assert_eq!(run_version(&binary, Duration::from_millis(200)), None);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_from_local_state() {
+        let json = json!({
+            "user_experience_metrics": { "stability": { "stats_version": "130.0.2849.80-64" } },
+            "variations_permanent_consistency_country": ["129.0.2792.89", "CN"]
+        });
+        assert_eq!(from_local_state(&json).as_deref(), Some("130.0.2849.80"));
+
+        let json = json!({ "variations_permanent_consistency_country": ["129.0.2792.89", "CN"] });
+        assert_eq!(from_local_state(&json).as_deref(), Some("129.0.2792.89"));
+
+        assert_eq!(from_local_state(&json!({})), None);
+    }
+
+    #[test]
+    fn test_version_ordering_and_ranges() {
+        let v130 = EdgeVersion::parse("130.0.2849.80-64").unwrap();
+        let v99 = EdgeVersion::parse("99.0.1150.55").unwrap();
+        assert!(v130 > v99);
+        assert_eq!(v130.to_string(), "130.0.2849.80");
+        assert_eq!(EdgeVersion::parse("unknown"), None);
+        assert_eq!(
+            parse_version_output("Microsoft Edge 131.0.2903.51 beta\n"),
+            EdgeVersion::parse("131.0.2903.51")
+        );
+        assert_eq!(parse_version_output("sleep (GNU coreutils) 9.4"), None);
+
+        let range = VersionRange {
+            min_major: Some(100),
+            max_major: Some(130),
+        };
+        assert!(range.contains(Some(&v130)));
+        assert!(!range.contains(Some(&v99)));
+        assert!(range.contains(None));
+        assert!(VersionRange::ALL.contains(Some(&v99)));
+    }
+}
@@ -0,0 +1,212 @@
+use std::time::{Duration, Instant};
+
+/// 默认的静默期：Edge 在更新或“重启以应用”时会自行重新启动，期间有短暂的未运行窗口
+pub const DEFAULT_QUIET_PERIOD: Duration = Duration::from_secs(5);
+
+/// Edge 运行时（或等待静默期时）的默认轮询间隔，尽快发现退出
+pub const DEFAULT_ACTIVE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Edge 刚关闭时的默认轮询间隔
+pub const DEFAULT_IDLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Edge 长时间关闭或会话锁定 / 空闲时的默认轮询间隔
+pub const DEFAULT_MAX_IDLE_INTERVAL: Duration = Duration::from_secs(20);
+
+/// Edge 每关闭这么长时间，空闲轮询间隔增加 1 秒（直到上限）
+const BACKOFF_RATIO: u32 = 10;
+
+/// [`run_service`](crate::run_service) 监控 Edge 的选项
+///
+/// 轮询间隔只用于 Windows / Linux 的轮询模式，macOS 使用事件通知。
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Edge 退出后需保持未运行的时长，之后才应用修复；期间 Edge 再次启动则取消本次修复
+    pub quiet_period: Duration,
+    /// Edge 运行时与等待静默期时的轮询间隔
+    pub active_interval: Duration,
+    /// Edge 刚关闭时的轮询间隔，之后随关闭时长逐渐增加
+    pub idle_interval: Duration,
+    /// 空闲轮询间隔的上限；会话锁定或空闲时直接使用
+    pub max_idle_interval: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            quiet_period: DEFAULT_QUIET_PERIOD,
+            active_interval: DEFAULT_ACTIVE_INTERVAL,
+            idle_interval: DEFAULT_IDLE_INTERVAL,
+            max_idle_interval: DEFAULT_MAX_IDLE_INTERVAL,
+        }
+    }
+}
+
+impl WatchOptions {
+    /// 下一次轮询前的等待时间
+    ///
+    /// - `active`：Edge 正在运行，或刚退出、仍在等待静默期
+    /// - `closed_for`：Edge 已关闭的时长
+    /// - `session_idle`：用户会话已锁定或空闲
+    pub(crate) fn poll_interval(
+        &self,
+        active: bool,
+        closed_for: Duration,
+        session_idle: bool,
+    ) -> Duration {
+        let max = self.max_idle_interval.max(self.idle_interval);
+        if active {
+            self.active_interval
+        } else if session_idle {
+            max
+        } else {
+            (closed_for / BACKOFF_RATIO).clamp(self.idle_interval, max)
+        }
+    }
+}
+
+/// [`ExitDebouncer::observe`] 的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExitEvent {
+    /// 无需处理
+    None,
+    /// Edge 刚退出，开始等待静默期
+    Pending,
+    /// Edge 在静默期内重新启动，取消待执行的修复
+    Cancelled,
+    /// Edge 已保持未运行一个静默期，应当应用修复
+    Confirmed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// 尚未观察到 Edge 运行，或已处理完上一次退出
+    Idle,
+    Running,
+    /// Edge 自该时刻起未运行
+    Pending(Instant),
+}
+
+/// Edge 退出检测的去抖状态机
+///
+/// 每次轮询把 Edge 是否运行与当前时间传给 [`observe`](Self::observe)，
+/// 只有 Edge 退出后保持未运行一个静默期才返回 [`ExitEvent::Confirmed`]。
+/// 时间由调用方传入，便于在测试中模拟。
+#[derive(Debug)]
+pub(crate) struct ExitDebouncer {
+    quiet_period: Duration,
+    phase: Phase,
+}
+
+impl ExitDebouncer {
+    pub(crate) fn new(quiet_period: Duration) -> Self {
+        Self {
+            quiet_period,
+            phase: Phase::Idle,
+        }
+    }
+
+    /// 记录一次观察结果，返回需要执行的动作
+    pub(crate) fn observe(&mut self, running: bool, now: Instant) -> ExitEvent {
+        match (self.phase, running) {
+            (Phase::Pending(_), true) => {
+                self.phase = Phase::Running;
+                ExitEvent::Cancelled
+            }
+            (_, true) => {
+                self.phase = Phase::Running;
+                ExitEvent::None
+            }
+            (Phase::Running, false) => {
+                self.phase = Phase::Pending(now);
+                self.confirm_if_quiet(now).unwrap_or(ExitEvent::Pending)
+            }
+            (Phase::Pending(_), false) => self.confirm_if_quiet(now).unwrap_or(ExitEvent::None),
+            (Phase::Idle, false) => ExitEvent::None,
+        }
+    }
+
+    /// Edge 是否刚退出、正在等待静默期
+    pub(crate) fn is_pending(&self) -> bool {
+        matches!(self.phase, Phase::Pending(_))
+    }
+
+    fn confirm_if_quiet(&mut self, now: Instant) -> Option<ExitEvent> {
+        let Phase::Pending(since) = self.phase else {
+            return None;
+        };
+        if now.saturating_duration_since(since) < self.quiet_period {
+            return None;
+        }
+        self.phase = Phase::Idle;
+        Some(ExitEvent::Confirmed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUIET: Duration = Duration::from_secs(5);
+
+    fn at(start: Instant, secs: u64) -> Instant {
+        start + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_exit_is_confirmed_after_quiet_period() {
+        let start = Instant::now();
+        let mut debouncer = ExitDebouncer::new(QUIET);
+
+        // 启动时 Edge 未运行：没有退出可处理
+        assert_eq!(debouncer.observe(false, start), ExitEvent::None);
+        assert_eq!(debouncer.observe(true, at(start, 2)), ExitEvent::None);
+        assert_eq!(debouncer.observe(false, at(start, 4)), ExitEvent::Pending);
+        assert_eq!(debouncer.observe(false, at(start, 6)), ExitEvent::None);
+        assert_eq!(debouncer.observe(false, at(start, 9)), ExitEvent::Confirmed);
+        // 同一次退出只触发一次
+        assert_eq!(debouncer.observe(false, at(start, 20)), ExitEvent::None);
+    }
+
+    #[test]
+    fn test_restart_within_quiet_period_cancels_the_fix() {
+        let start = Instant::now();
+        let mut debouncer = ExitDebouncer::new(QUIET);
+
+        debouncer.observe(true, start);
+        assert_eq!(debouncer.observe(false, at(start, 2)), ExitEvent::Pending);
+        assert_eq!(debouncer.observe(true, at(start, 4)), ExitEvent::Cancelled);
+        assert_eq!(debouncer.observe(true, at(start, 6)), ExitEvent::None);
+
+        // 重新启动的实例退出后重新计时
+        assert_eq!(debouncer.observe(false, at(start, 8)), ExitEvent::Pending);
+        assert_eq!(debouncer.observe(false, at(start, 12)), ExitEvent::None);
+        assert_eq!(
+            debouncer.observe(false, at(start, 13)),
+            ExitEvent::Confirmed
+        );
+    }
+
+    #[test]
+    fn test_poll_interval_backs_off_while_edge_is_closed() {
+        let options = WatchOptions::default();
+        let interval = |active, closed_secs, idle| {
+            options.poll_interval(active, Duration::from_secs(closed_secs), idle)
+        };
+
+        assert_eq!(interval(true, 0, false), Duration::from_millis(500));
+        assert_eq!(interval(true, 0, true), Duration::from_millis(500));
+        assert_eq!(interval(false, 5, false), Duration::from_secs(2));
+        assert_eq!(interval(false, 60, false), Duration::from_secs(6));
+        assert_eq!(interval(false, 3600, false), Duration::from_secs(20));
+        assert_eq!(interval(false, 5, true), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn test_zero_quiet_period_confirms_immediately() {
+        let start = Instant::now();
+        let mut debouncer = ExitDebouncer::new(Duration::ZERO);
+
+        debouncer.observe(true, start);
+        assert_eq!(debouncer.observe(false, at(start, 2)), ExitEvent::Confirmed);
+    }
+}
@@ -0,0 +1,549 @@
+//! `apply` 命令的端到端测试
+
+#![cfg(unix)]
+
+mod fixtures;
+
+use fixtures::*;
+use serde_json::json;
+use std::fs;
+
+#[test]
+fn apply_patches_every_channel_and_profile() {
+    let (fixture, channels) = Fixture::realistic();
+
+    let output = fixture.apply();
+    assert!(output.status.success(), "{}", logs(&output));
+
+    for channel in &channels {
+        let local_state = read_json(&channel.local_state());
+        assert_eq!(local_state["variations_country"], json!("US"));
+        assert_eq!(local_state["profile"]["last_used"], json!("Default"));
+
+        for profile in PROFILES {
+            let prefs = read_json(&channel.preferences(profile));
+            assert_eq!(prefs["browser"]["chat_ip_eligibility_status"], json!(true));
+            assert_eq!(prefs["browser"]["has_seen_welcome_page"], json!(true));
+            assert_eq!(prefs["profile"]["name"], json!(profile));
+        }
+    }
+
+    let log = logs(&output);
+    assert_eq!(
+        log.matches("fix applied to Local State").count(),
+        channels.len()
+    );
+    assert_eq!(
+        log.matches("fix applied to Preferences").count(),
+        channels.len() * PROFILES.len()
+    );
+}
+
+#[test]
+fn apply_preserves_compact_format_and_key_order() {
+    let (fixture, channels) = Fixture::realistic();
+
+    let output = fixture.apply();
+    assert!(output.status.success(), "{}", logs(&output));
+
+    let local_state = fs::read_to_string(channels[0].local_state()).unwrap();
+    assert_eq!(
+        local_state,
+        local_state_json("CN").replace(
+            r#""variations_country":"CN""#,
+            r#""variations_country":"US""#
+        )
+    );
+
+    let prefs = fs::read_to_string(channels[0].preferences("Default")).unwrap();
+    assert_eq!(
+        prefs,
+        preferences_json("Default").replace(
+            r#""top":0}}"#,
+            r#""top":0},"chat_ip_eligibility_status":true}"#
+        )
+    );
+}
+
+#[test]
+fn apply_leaves_non_profile_directories_untouched() {
+    let (fixture, channels) = Fixture::realistic();
+
+    let output = fixture.apply();
+    assert!(output.status.success(), "{}", logs(&output));
+
+    for channel in &channels {
+        for dir in NON_PROFILE_DIRS {
+            let content = fs::read_to_string(channel.preferences(dir)).unwrap();
+            assert_eq!(content, preferences_json(dir));
+        }
+    }
+}
+
+#[test]
+fn apply_is_idempotent() {
+    let (fixture, channels) = Fixture::realistic();
+
+    assert!(fixture.apply().status.success());
+    let first = fs::read_to_string(channels[0].preferences("Default")).unwrap();
+
+    let output = fixture.apply();
+    assert!(output.status.success(), "{}", logs(&output));
+    assert!(logs(&output).contains("No changes needed"));
+    assert_eq!(
+        fs::read_to_string(channels[0].preferences("Default")).unwrap(),
+        first
+    );
+}
+
+#[test]
+fn apply_without_edge_data_warns_and_succeeds() {
+    let fixture = Fixture::new();
+
+    let output = fixture.apply();
+    assert!(output.status.success(), "{}", logs(&output));
+    assert!(logs(&output).contains("Edge configuration files not found"));
+}
+
+#[test]
+fn apply_honours_user_data_dir_override() {
+    let fixture = Fixture::new();
+    let custom = fixture.channel("custom/edge");
+    let prefs = custom.write_preferences("Default", &preferences_json("Default"));
+    let ignored = fixture
+        .channel(USER_DATA_PATHS[0])
+        .write_preferences("Default", &preferences_json("Default"));
+
+    let output = fixture
+        .command(&["apply", "--user-data-dir", "custom/edge"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", logs(&output));
+
+    assert_eq!(
+        read_json(&prefs)["browser"]["chat_ip_eligibility_status"],
+        json!(true)
+    );
+    assert_eq!(
+        fs::read_to_string(&ignored).unwrap(),
+        preferences_json("Default")
+    );
+}
+
+#[test]
+fn apply_reports_malformed_json_without_touching_it() {
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    let malformed = r#"{"browser":{"window_placement":"#;
+    let prefs = channel.write_preferences("Profile 1", malformed);
+
+    let output = fixture.apply();
+    assert!(!output.status.success());
+    assert!(
+        logs(&output).contains("Failed to parse JSON"),
+        "{}",
+        logs(&output)
+    );
+    assert_eq!(fs::read_to_string(&prefs).unwrap(), malformed);
+}
+
+#[test]
+fn apply_reports_read_only_files() {
+    use std::os::unix::fs::PermissionsExt;
+
+    if running_as_root() {
+        eprintln!("skipping: file permissions are not enforced for root");
+        return;
+    }
+
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    let prefs = channel.write_preferences("Default", &preferences_json("Default"));
+    fs::set_permissions(&prefs, fs::Permissions::from_mode(0o444)).unwrap();
+
+    let output = fixture.apply();
+    assert!(!output.status.success());
+    assert!(
+        logs(&output).contains("Failed to write"),
+        "{}",
+        logs(&output)
+    );
+    assert_eq!(
+        fs::read_to_string(&prefs).unwrap(),
+        preferences_json("Default")
+    );
+}
+
+#[test]
+fn apply_follows_symlinked_user_data_dir() {
+    let fixture = Fixture::new();
+    let real = fixture.channel("elsewhere/microsoft-edge");
+    let prefs = real.write_preferences("Default", &preferences_json("Default"));
+
+    let link = fixture.home().join(USER_DATA_PATHS[0]);
+    fs::create_dir_all(link.parent().unwrap()).unwrap();
+    std::os::unix::fs::symlink(&real.dir, &link).unwrap();
+
+    let output = fixture.apply();
+    assert!(output.status.success(), "{}", logs(&output));
+    assert_eq!(
+        read_json(&prefs)["browser"]["chat_ip_eligibility_status"],
+        json!(true)
+    );
+}
+
+#[test]
+fn apply_skips_dangling_preferences_symlink() {
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    let prefs = channel.preferences("Default");
+    fs::create_dir_all(prefs.parent().unwrap()).unwrap();
+    std::os::unix::fs::symlink(fixture.home().join("missing"), &prefs).unwrap();
+
+    let output = fixture.apply();
+    assert!(output.status.success(), "{}", logs(&output));
+    assert!(!fixture.home().join("missing").exists());
+}
+
+#[test]
+fn apply_refuses_preferences_symlinked_outside_user_data_dir() {
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    let outside = fixture.home().join("outside.json");
+    fs::write(&outside, preferences_json("Default")).unwrap();
+    let prefs = channel.preferences("Default");
+    fs::create_dir_all(prefs.parent().unwrap()).unwrap();
+    std::os::unix::fs::symlink(&outside, &prefs).unwrap();
+
+    // 唯一找到的文件被拒绝：什么都没有修复，以非零退出码结束
+    let output = fixture.command(&["apply", "--json"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(12), "{}", logs(&output));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["success"], json!(false));
+    assert_eq!(report["files"][0]["outcome"], json!("skipped"));
+    assert_eq!(report["files"][0]["security"], json!(true));
+    assert_eq!(
+        fs::read_to_string(&outside).unwrap(),
+        preferences_json("Default")
+    );
+}
+
+#[test]
+fn apply_refuses_files_owned_by_another_user() {
+    if !running_as_root() {
+        eprintln!("skipping: changing file ownership requires root");
+        return;
+    }
+
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    let prefs = channel.write_preferences("Default", &preferences_json("Default"));
+    std::os::unix::fs::chown(&prefs, Some(65534), None).unwrap();
+
+    let output = fixture.apply();
+    assert_eq!(output.status.code(), Some(12), "{}", logs(&output));
+    assert!(
+        logs(&output).contains("owned by UID 65534"),
+        "{}",
+        logs(&output)
+    );
+    assert_eq!(
+        fs::read_to_string(&prefs).unwrap(),
+        preferences_json("Default")
+    );
+}
+
+#[test]
+fn apply_as_root_patches_another_users_home() {
+    if !running_as_root() {
+        eprintln!("skipping: changing file ownership requires root");
+        return;
+    }
+
+    // 以 root 身份通过 --home 修复其他用户的主目录：文件与用户数据目录同属该用户
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    let prefs = channel.write_preferences("Default", &preferences_json("Default"));
+    std::os::unix::fs::chown(&channel.dir, Some(65534), None).unwrap();
+    std::os::unix::fs::chown(&prefs, Some(65534), None).unwrap();
+
+    let home = fixture.home().to_str().unwrap();
+    let output = fixture
+        .command(&["apply", "--home", home])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", logs(&output));
+    assert_eq!(
+        read_json(&prefs)["browser"]["chat_ip_eligibility_status"],
+        json!(true)
+    );
+}
+
+#[test]
+fn apply_handles_huge_preferences() {
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    let prefs = channel.write_preferences("Default", &huge_preferences_json(16 << 20));
+
+    let output = fixture.apply();
+    assert!(output.status.success(), "{}", logs(&output));
+
+    let json = read_json(&prefs);
+    assert_eq!(json["browser"]["chat_ip_eligibility_status"], json!(true));
+    assert!(json["extensions"]["settings"].as_object().unwrap().len() > 16_000);
+}
+
+#[test]
+fn apply_reports_mac_protected_keys_without_touching_secure_preferences() {
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    channel.write_preferences("Default", &preferences_json("Default"));
+    let secure = channel.write_secure_preferences("Default", &secure_preferences_json(false));
+
+    let output = fixture.apply();
+    assert!(output.status.success(), "{}", logs(&output));
+
+    let log = logs(&output);
+    assert!(log.contains("is MAC-protected in"), "{log}");
+    assert!(log.contains("--secure-preferences"), "{log}");
+    assert_eq!(
+        fs::read_to_string(&secure).unwrap(),
+        secure_preferences_json(false)
+    );
+}
+
+#[test]
+fn apply_patches_secure_preferences_when_opted_in() {
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    channel.write_preferences("Default", &preferences_json("Default"));
+    let secure = channel.write_secure_preferences("Default", &secure_preferences_json(false));
+
+    let output = fixture
+        .command(&["apply", "--secure-preferences"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", logs(&output));
+    assert_eq!(
+        fs::read_to_string(&secure).unwrap(),
+        secure_preferences_json(true)
+    );
+}
+
+#[test]
+fn revert_restores_original_files() {
+    let (fixture, channels) = Fixture::realistic();
+
+    assert!(fixture.apply().status.success());
+    assert_ne!(
+        fs::read_to_string(channels[0].local_state()).unwrap(),
+        local_state_json("CN")
+    );
+
+    let output = fixture.command(&["revert"]).output().unwrap();
+    assert!(output.status.success(), "{}", logs(&output));
+
+    for channel in &channels {
+        assert_eq!(
+            fs::read_to_string(channel.local_state()).unwrap(),
+            local_state_json("CN")
+        );
+        for profile in PROFILES {
+            assert_eq!(
+                fs::read_to_string(channel.preferences(profile)).unwrap(),
+                preferences_json(profile)
+            );
+        }
+    }
+
+    // 状态已清空，再次 revert 无事可做
+    let output = fixture.command(&["revert"]).output().unwrap();
+    assert!(
+        logs(&output).contains("Nothing to revert"),
+        "{}",
+        logs(&output)
+    );
+}
+
+#[test]
+fn revert_keeps_the_first_recorded_value() {
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    channel.write_local_state(&local_state_json("CN"));
+
+    assert!(fixture.apply().status.success());
+    // Edge 重新下发了区域，再次修复后 revert 仍应恢复最初的值
+    channel.write_local_state(&local_state_json("SG"));
+    assert!(fixture.apply().status.success());
+
+    let output = fixture.command(&["revert"]).output().unwrap();
+    assert!(output.status.success(), "{}", logs(&output));
+    assert_eq!(
+        read_json(&channel.local_state())["variations_country"],
+        json!("CN")
+    );
+}
+
+#[test]
+fn status_reports_resets_and_failures() {
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    channel.write_local_state(&local_state_json("CN"));
+
+    assert!(fixture.apply().status.success());
+    // Edge 重新下发了区域
+    channel.write_local_state(&local_state_json("SG"));
+    let output = fixture.apply();
+    assert!(
+        logs(&output).contains("1 time(s) so far"),
+        "{}",
+        logs(&output)
+    );
+    channel.write_preferences("Profile 1", r#"{"browser":"#);
+    assert!(!fixture.apply().status.success());
+
+    let output = fixture.command(&["status"]).output().unwrap();
+    assert!(output.status.success(), "{}", logs(&output));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Helper: not running"), "{stdout}");
+    assert!(
+        stdout.contains("Edge version:     130.0.2849.80"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("Patched:          2 time(s)"), "{stdout}");
+    assert!(stdout.contains("Reset by Edge:    1 time(s)"), "{stdout}");
+    assert!(
+        stdout.contains(r#"Previous variations_country: "SG""#),
+        "{stdout}"
+    );
+    assert!(stdout.contains("Failures:         1"), "{stdout}");
+    assert!(stdout.contains("Failed to parse JSON"), "{stdout}");
+}
+
+#[test]
+fn apply_warns_about_untested_edge_versions() {
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    channel.write_local_state(&local_state_json("CN").replace("130.0.2849.80", "999.0.1.2"));
+
+    let output = fixture.apply();
+    assert!(output.status.success(), "{}", logs(&output));
+    assert!(
+        logs(&output).contains("Edge 999.0.1.2"),
+        "{}",
+        logs(&output)
+    );
+    assert!(logs(&output).contains("newer than the latest tested version"));
+
+    let output = fixture.command(&["status"]).output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Edge version:     999.0.1.2"));
+}
+
+#[test]
+fn apply_continues_past_a_corrupt_profile() {
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    channel.write_local_state(&local_state_json("CN"));
+    channel.write_preferences("Default", &preferences_json("Default"));
+    channel.write_preferences("Profile 1", r#"{"browser":"#);
+    channel.write_preferences("Profile 2", &preferences_json("Profile 2"));
+
+    let output = fixture.apply();
+    assert_eq!(output.status.code(), Some(11));
+    let logs = logs(&output);
+    assert!(logs.contains("[E_PARSE]"), "{logs}");
+    assert!(
+        logs.contains("3 patched, 0 unchanged, 0 skipped, 1 failed"),
+        "{logs}"
+    );
+
+    assert_eq!(
+        read_json(&channel.local_state())["variations_country"],
+        json!("US")
+    );
+    for profile in ["Default", "Profile 2"] {
+        assert_eq!(
+            read_json(&channel.preferences(profile))["browser"]["chat_ip_eligibility_status"],
+            json!(true)
+        );
+    }
+}
+
+#[test]
+fn apply_json_reports_error_codes() {
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    channel.write_local_state(&local_state_json("US"));
+    channel.write_preferences("Default", r#"{"browser":"#);
+
+    let output = fixture.command(&["apply", "--json"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(11));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["success"], json!(false));
+    assert_eq!(report["summary"]["unchanged"], json!(1));
+    assert_eq!(report["files"][1]["outcome"], json!("failed"));
+    assert_eq!(report["files"][1]["error"]["code"], json!("E_PARSE"));
+
+    // 整体失败（找不到根目录）也输出 JSON
+    let output = fixture
+        .command(&[
+            "apply",
+            "--json",
+            "--home",
+            "/nonexistent/edge-copilot-helper",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["error"]["code"], json!("E_DISCOVERY"));
+}
+
+#[test]
+fn apply_repairs_corrupt_files_when_opted_in() {
+    let fixture = Fixture::new();
+    let channel = fixture.channel(USER_DATA_PATHS[0]);
+    channel.write_local_state(&local_state_json("CN"));
+    let prefs = channel.write_preferences("Default", &preferences_json("Default"));
+    assert!(fixture.apply().status.success());
+
+    // 首次修改时保存的备份可用于恢复
+    fs::write(&prefs, r#"{"browser":"#).unwrap();
+    let output = fixture.command(&["apply", "--repair"]).output().unwrap();
+    assert!(output.status.success(), "{}", logs(&output));
+    assert!(
+        logs(&output).contains("restored it from"),
+        "{}",
+        logs(&output)
+    );
+    assert_eq!(
+        read_json(&prefs)["browser"]["chat_ip_eligibility_status"],
+        json!(true)
+    );
+
+    // 没有可用副本时移走损坏的文件，由 Edge 重新生成
+    let other = fixture.channel(USER_DATA_PATHS[1]);
+    let broken = other.write_local_state("\0\0");
+    fs::write(broken.with_file_name("Local State.bak"), "{").unwrap();
+    let output = fixture.command(&["apply", "--repair"]).output().unwrap();
+    assert!(output.status.success(), "{}", logs(&output));
+    assert!(logs(&output).contains("1 skipped"), "{}", logs(&output));
+    assert!(!broken.exists());
+    let quarantined = fs::read_dir(broken.parent().unwrap())
+        .unwrap()
+        .flatten()
+        .any(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("Local State.corrupt-")
+        });
+    assert!(quarantined);
+
+    // 不加 --repair 时保持原样并提示
+    let corrupt = other.write_local_state("{");
+    let output = fixture.apply();
+    assert_eq!(output.status.code(), Some(11));
+    assert!(logs(&output).contains("pass --repair"), "{}", logs(&output));
+    assert_eq!(fs::read_to_string(corrupt).unwrap(), "{");
+}
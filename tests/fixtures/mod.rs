@@ -0,0 +1,174 @@
+//! 集成测试用的模拟 Edge 用户数据目录
+//!
+//! 每个 [`Fixture`] 是一个独立的临时主目录，可以在其中生成各 Edge 版本的用户数据目录、
+//! 多个 Profile，以及损坏的 JSON、只读文件、符号链接和超大 Preferences 等异常情况。
+
+// 每个集成测试文件只用到其中一部分
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+pub use edge_copilot_helper::constants::edge::USER_DATA_PATHS;
+
+/// Edge 会创建、但不属于用户 Profile 的目录（不应被修改）
+pub const NON_PROFILE_DIRS: &[&str] = &["System Profile", "Guest Profile", "Crashpad"];
+
+/// 用户 Profile 目录
+pub const PROFILES: &[&str] = &["Default", "Profile 1", "Profile 2"];
+
+/// 一个临时主目录
+pub struct Fixture {
+    root: TempDir,
+}
+
+/// 一个 Edge 版本的用户数据目录
+pub struct Channel {
+    pub dir: PathBuf,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Self {
+            root: tempfile::tempdir().expect("create temp dir"),
+        }
+    }
+
+    /// 生成完整的模拟环境：四个 Edge 版本，每个版本包含若干 Profile 和非 Profile 目录
+    pub fn realistic() -> (Self, Vec<Channel>) {
+        let fixture = Self::new();
+        let channels = USER_DATA_PATHS
+            .iter()
+            .map(|user_data| {
+                let channel = fixture.channel(user_data);
+                channel.write_local_state(&local_state_json("CN"));
+                for profile in PROFILES.iter().chain(NON_PROFILE_DIRS) {
+                    channel.write_preferences(profile, &preferences_json(profile));
+                }
+                channel
+            })
+            .collect();
+        (fixture, channels)
+    }
+
+    /// 模拟的用户主目录
+    pub fn home(&self) -> &Path {
+        self.root.path()
+    }
+
+    /// 创建一个用户数据目录（相对于主目录）
+    pub fn channel(&self, user_data: &str) -> Channel {
+        let dir = self.home().join(user_data);
+        fs::create_dir_all(&dir).expect("create user data dir");
+        Channel { dir }
+    }
+
+    /// 以该主目录运行 helper，所有运行期文件（锁、日志）都写在临时目录内
+    pub fn command(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_edge-copilot-helper"));
+        cmd.args(args)
+            .env("EDGE_COPILOT_HELPER_ROOT", self.home())
+            .env("HOME", self.home())
+            .env("XDG_DATA_HOME", self.home().join(".local/share"))
+            .env("XDG_CONFIG_HOME", self.home().join(".config"))
+            .env_remove("NOTIFY_SOCKET")
+            .env_remove("RUST_LOG");
+        cmd
+    }
+
+    /// 执行 `apply` 并返回输出
+    pub fn apply(&self) -> Output {
+        self.command(&["apply"]).output().expect("run apply")
+    }
+}
+
+impl Channel {
+    pub fn local_state(&self) -> PathBuf {
+        self.dir.join("Local State")
+    }
+
+    pub fn preferences(&self, profile: &str) -> PathBuf {
+        self.dir.join(profile).join("Preferences")
+    }
+
+    pub fn write_local_state(&self, content: &str) -> PathBuf {
+        let path = self.local_state();
+        fs::write(&path, content).expect("write Local State");
+        path
+    }
+
+    pub fn secure_preferences(&self, profile: &str) -> PathBuf {
+        self.dir.join(profile).join("Secure Preferences")
+    }
+
+    pub fn write_secure_preferences(&self, profile: &str, content: &str) -> PathBuf {
+        let path = self.secure_preferences(profile);
+        fs::create_dir_all(path.parent().unwrap()).expect("create profile dir");
+        fs::write(&path, content).expect("write Secure Preferences");
+        path
+    }
+
+    pub fn write_preferences(&self, profile: &str, content: &str) -> PathBuf {
+        let path = self.preferences(profile);
+        fs::create_dir_all(path.parent().unwrap()).expect("create profile dir");
+        fs::write(&path, content).expect("write Preferences");
+        path
+    }
+}
+
+/// 与 Edge 写出的格式一致的单行 `Local State`
+pub fn local_state_json(country: &str) -> String {
+    format!(
+        r#"{{"browser":{{"enabled_labs_experiments":[],"last_redirect_origin":""}},"profile":{{"info_cache":{{"Default":{{"name":"Personal"}}}},"last_used":"Default"}},"variations_country":"{country}","variations_permanent_consistency_country":["130.0.2849.80","{country}"]}}"#
+    )
+}
+
+/// 与 Edge 写出的格式一致的单行 `Preferences`
+pub fn preferences_json(profile: &str) -> String {
+    format!(
+        r#"{{"browser":{{"has_seen_welcome_page":true,"window_placement":{{"bottom":1040,"left":0,"maximized":true,"right":1920,"top":0}}}},"extensions":{{"settings":{{}}}},"profile":{{"name":"{profile}"}}}}"#
+    )
+}
+
+/// 包含 `protection.macs` 且保存了被修复键的 `Secure Preferences`
+pub fn secure_preferences_json(chat_ip_eligibility_status: bool) -> String {
+    format!(
+        r#"{{"browser":{{"chat_ip_eligibility_status":{chat_ip_eligibility_status}}},"protection":{{"macs":{{"browser":{{"chat_ip_eligibility_status":"9A1B3C5D7E9F"}}}},"super_mac":"0F1E2D3C"}}}}"#
+    )
+}
+
+/// 生成至少 `min_bytes` 大小的 `Preferences`（大量扩展设置）
+pub fn huge_preferences_json(min_bytes: usize) -> String {
+    let entry = format!(r#"{{"manifest":{{"description":"{}"}}}}"#, "x".repeat(1000));
+    let count = min_bytes / entry.len() + 1;
+    let settings = (0..count)
+        .map(|i| format!(r#""ext{i:08}":{entry}"#))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(r#"{{"browser":{{}},"extensions":{{"settings":{{{settings}}}}}}}"#)
+}
+
+pub fn read_json(path: &Path) -> Value {
+    let content = fs::read_to_string(path).expect("read JSON file");
+    serde_json::from_str(&content).expect("parse JSON file")
+}
+
+/// 进程的全部日志输出（stdout + stderr）
+pub fn logs(output: &Output) -> String {
+    format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    )
+}
+
+/// 以 root 运行时文件权限不生效，依赖只读文件的测试需要跳过
+#[cfg(unix)]
+pub fn running_as_root() -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let probe = tempfile::NamedTempFile::new().expect("create probe file");
+    probe.as_file().metadata().expect("stat probe file").uid() == 0
+}
@@ -0,0 +1,247 @@
+//! `run` 命令（轮询监控）的端到端测试
+//!
+//! 用改名为 `msedge` 的 `sleep` 进程模拟 Edge，验证其退出后 helper 会自动修复配置。
+
+#![cfg(target_os = "linux")]
+
+mod fixtures;
+
+use fixtures::*;
+use serde_json::json;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 等待日志出现的最长时间（Edge 关闭时轮询间隔从 2 秒开始增加，默认静默期为 5 秒）
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 运行中的 helper，drop 时结束进程
+struct Helper {
+    child: Child,
+    lines: Receiver<String>,
+}
+
+impl Helper {
+    fn spawn(fixture: &Fixture) -> Self {
+        Self::spawn_with(fixture, &["run"])
+    }
+
+    fn spawn_with(fixture: &Fixture, args: &[&str]) -> Self {
+        let mut child = fixture
+            .command(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("spawn helper");
+
+        let (tx, lines) = mpsc::channel();
+        let stdout = child.stdout.take().unwrap();
+        let stderr = child.stderr.take().unwrap();
+        for stream in [
+            Box::new(stdout) as Box<dyn std::io::Read + Send>,
+            Box::new(stderr),
+        ] {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                    let _ = tx.send(line);
+                }
+            });
+        }
+
+        Self { child, lines }
+    }
+
+    /// 等待包含 `needle` 的日志行
+    fn wait_for(&self, needle: &str) -> String {
+        let deadline = Instant::now() + TIMEOUT;
+        let mut seen = Vec::new();
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match self.lines.recv_timeout(remaining) {
+                Ok(line) if line.contains(needle) => return line,
+                Ok(line) => seen.push(line),
+                Err(_) => break,
+            }
+        }
+        panic!(
+            "timed out waiting for {needle:?}; output:\n{}",
+            seen.join("\n")
+        );
+    }
+}
+
+impl Drop for Helper {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// 启动一个进程名为 `msedge` 的假 Edge
+fn spawn_fake_edge(fixture: &Fixture) -> Child {
+    let fake = fixture.home().join("msedge");
+    std::fs::copy("/bin/sleep", &fake).expect("copy sleep binary");
+    Command::new(&fake)
+        .arg("60")
+        .spawn()
+        .expect("spawn fake Edge")
+}
+
+#[test]
+fn run_applies_fix_after_edge_exits() {
+    let (fixture, channels) = Fixture::realistic();
+    let helper = Helper::spawn(&fixture);
+    helper.wait_for("Polling Mode");
+
+    let mut edge = spawn_fake_edge(&fixture);
+    // 至少经过一次轮询，让 helper 观察到 Edge 正在运行
+    thread::sleep(Duration::from_secs(3));
+    assert_eq!(
+        std::fs::read_to_string(channels[0].local_state()).unwrap(),
+        local_state_json("CN")
+    );
+
+    edge.kill().unwrap();
+    edge.wait().unwrap();
+    helper.wait_for("Edge exited");
+    helper.wait_for("fix applied to Preferences");
+
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let patched = channels.iter().all(|channel| {
+            read_json(&channel.local_state())["variations_country"] == json!("US")
+                && PROFILES.iter().all(|profile| {
+                    read_json(&channel.preferences(profile))["browser"]
+                        ["chat_ip_eligibility_status"]
+                        == json!(true)
+                })
+        });
+        if patched {
+            break;
+        }
+        assert!(Instant::now() < deadline, "not every profile was patched");
+        thread::sleep(Duration::from_millis(200));
+    }
+}
+
+#[test]
+fn run_skips_the_fix_when_edge_restarts_within_the_quiet_period() {
+    let (fixture, channels) = Fixture::realistic();
+    let helper = Helper::spawn_with(&fixture, &["run", "--quiet-period", "8"]);
+    helper.wait_for("Polling Mode");
+
+    let mut edge = spawn_fake_edge(&fixture);
+    thread::sleep(Duration::from_secs(3));
+    edge.kill().unwrap();
+    edge.wait().unwrap();
+    helper.wait_for("Waiting 8s in case it restarts");
+
+    // 模拟 Edge 更新后自行重新启动
+    let mut edge = spawn_fake_edge(&fixture);
+    helper.wait_for("skipping the fix");
+    assert_eq!(
+        std::fs::read_to_string(channels[0].local_state()).unwrap(),
+        local_state_json("CN")
+    );
+
+    edge.kill().unwrap();
+    edge.wait().unwrap();
+    helper.wait_for("Applying fix");
+    helper.wait_for("fix applied to Local State");
+}
+
+#[test]
+fn run_refuses_a_second_instance() {
+    let fixture = Fixture::new();
+    let helper = Helper::spawn(&fixture);
+    helper.wait_for("Polling Mode");
+
+    let output = fixture.command(&["run"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(9));
+    assert!(
+        logs(&output).contains("Another instance is already running"),
+        "{}",
+        logs(&output)
+    );
+    assert!(
+        logs(&output).contains(&format!("PID {}", helper.child.id())),
+        "{}",
+        logs(&output)
+    );
+}
+
+#[test]
+fn run_replace_takes_over_from_the_running_instance() {
+    let fixture = Fixture::new();
+    let mut old = Helper::spawn(&fixture);
+    old.wait_for("Polling Mode");
+    let old_pid = old.child.id();
+
+    let new = Helper::spawn_with(&fixture, &["run", "--replace"]);
+    new.wait_for(&format!("Took over from PID {old_pid}"));
+    new.wait_for("Polling Mode");
+
+    let deadline = Instant::now() + TIMEOUT;
+    while old.child.try_wait().unwrap().is_none() {
+        assert!(Instant::now() < deadline, "old instance is still running");
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn revert_gives_up_when_edge_stays_open() {
+    let (fixture, channels) = Fixture::realistic();
+    assert!(fixture.apply().status.success());
+    let patched = std::fs::read_to_string(channels[0].local_state()).unwrap();
+
+    let mut edge = spawn_fake_edge(&fixture);
+    let output = fixture
+        .command(&["revert", "--wait-timeout", "1"])
+        .output()
+        .unwrap();
+    edge.kill().unwrap();
+    edge.wait().unwrap();
+
+    assert!(!output.status.success());
+    assert!(
+        logs(&output).contains("Edge is still running after 1s"),
+        "{}",
+        logs(&output)
+    );
+    assert_eq!(
+        std::fs::read_to_string(channels[0].local_state()).unwrap(),
+        patched
+    );
+}
+
+/// 向 helper 发送信号
+fn send_signal(helper: &Helper, signal: &str) {
+    let status = Command::new("kill")
+        .args([&format!("-{signal}"), &helper.child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn run_shuts_down_cleanly_on_sigterm_and_sigint() {
+    for signal in ["TERM", "INT"] {
+        let fixture = Fixture::new();
+        let mut helper = Helper::spawn(&fixture);
+        helper.wait_for("Polling Mode");
+
+        send_signal(&helper, signal);
+        helper.wait_for("Shutting down: received termination signal");
+        let status = helper.child.wait().unwrap();
+        assert!(status.success(), "SIG{signal}: {status}");
+
+        // 单实例锁已释放
+        let output = fixture.command(&["status"]).output().unwrap();
+        assert!(
+            String::from_utf8_lossy(&output.stdout).contains("Helper: not running"),
+            "SIG{signal}"
+        );
+    }
+}